@@ -0,0 +1,44 @@
+//! Drive the `futures-io` traits without depending on a particular runtime.
+
+use std::{
+    future::poll_fn,
+    io::{self, ErrorKind},
+    pin::Pin,
+};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::error::{Error, ErrorCode, Result};
+
+/// Reads exactly enough bytes from the stream to fill the buffer, which starts at the given offset of the frame.
+pub(crate) async fn read_exact<R>(reader: &mut R, buf: &mut [u8], offset: usize) -> Result<()>
+where
+    R: ?Sized + AsyncRead + Unpin,
+{
+    let mut pos = 0;
+    while pos < buf.len() {
+        match poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, &mut buf[pos..])).await {
+            Ok(0) => return Err(Error::data(ErrorCode::EofWhileParsingPacket, None, Some(offset + pos))),
+            Ok(n) => pos += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {},
+            Err(e) => return Err(Error::io(e, Some(offset + pos))),
+        }
+    }
+    Ok(())
+}
+
+/// Writes the whole buffer to the stream and flushes it.
+pub(crate) async fn write_all<W>(writer: &mut W, mut buf: &[u8]) -> io::Result<()>
+where
+    W: ?Sized + AsyncWrite + Unpin,
+{
+    while !buf.is_empty() {
+        match poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => buf = &buf[n..],
+            Err(e) if e.kind() == ErrorKind::Interrupted => {},
+            Err(e) => return Err(e),
+        }
+    }
+    poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await
+}
@@ -0,0 +1,252 @@
+//! Print an annotated breakdown of VICI packets, messages, or recordings given as hex or binary.
+
+use std::{
+    env, error, fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+
+use serde_vici::{inspect::Dump, record::Recording};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error + Send + Sync>>;
+
+const USAGE: &str = "\
+Usage: vici-dump [OPTIONS] [FILE]
+
+Prints an annotated breakdown of the VICI packets in FILE, or the standard input if FILE is omitted or `-`.
+
+Options:
+  -f, --format <FORMAT>  The format of the input: hex, binary [default: hex if the input is hex, binary otherwise]
+  -t, --type <TYPE>      What the input contains: packets, message, recording [default: packets]
+  -h, --help             Prints this help
+";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Hex,
+    Binary,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Contents {
+    Packets,
+    Message,
+    Recording,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<_> = env::args().skip(1).collect();
+    match run(&args, &mut io::stdin().lock(), &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("vici-dump: {e}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run(args: &[String], stdin: &mut impl Read, out: &mut impl Write) -> Result<()> {
+    let mut format = None;
+    let mut contents = Contents::Packets;
+    let mut file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                out.write_all(USAGE.as_bytes())?;
+                return Ok(());
+            },
+            "-f" | "--format" => format = Some(value()?.parse()?),
+            "-t" | "--type" => contents = value()?.parse()?,
+            option if option.starts_with('-') && option != "-" => return Err(format!("unknown option: {option}").into()),
+            _ if file.is_some() => return Err(format!("unexpected argument: {arg}").into()),
+            _ => file = Some(arg.as_str()),
+        }
+    }
+
+    let mut input = vec![];
+    match file {
+        None | Some("-") => stdin.read_to_end(&mut input).map(drop)?,
+        Some(file) => input = fs::read(file)?,
+    }
+
+    let input = match format {
+        Some(Format::Hex) => parse_hex(&input)?,
+        Some(Format::Binary) => input,
+        None => parse_hex(&input).unwrap_or(input),
+    };
+
+    let malformed = match contents {
+        Contents::Packets => print(out, &Dump::packets(&input))?,
+        Contents::Message => print(out, &Dump::message(&input))?,
+        Contents::Recording => match serde_vici::from_slice::<Recording>(&input) {
+            Ok(recording) => {
+                let mut malformed = false;
+                for (i, frame) in recording.frames.iter().enumerate() {
+                    let timestamp = frame.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+                    let name = frame.name.as_deref().map(|name| format!(" {name}")).unwrap_or_default();
+                    writeln!(
+                        out,
+                        "frame {i}: {:?} {}{name} at {}.{:06}",
+                        frame.direction,
+                        frame.packet_type,
+                        timestamp.as_secs(),
+                        timestamp.subsec_micros(),
+                    )?;
+                    malformed |= print(out, &Dump::message(&frame.body))?;
+                }
+                malformed
+            },
+            // The breakdown of the recording itself shows where it is malformed.
+            Err(_) => print(out, &Dump::message(&input))?,
+        },
+    };
+
+    if malformed {
+        return Err("the input is malformed".into());
+    }
+    Ok(())
+}
+
+/// Prints the breakdown and returns true if the input is malformed.
+fn print(out: &mut impl Write, dump: &Dump) -> Result<bool> {
+    write!(out, "{dump}")?;
+    Ok(dump.error().is_some())
+}
+
+/// Parses hex digits, ignoring whitespace, separators, and `0x` prefixes.
+fn parse_hex(input: &[u8]) -> Result<Vec<u8>> {
+    let input = std::str::from_utf8(input)?.replace("0x", "");
+    let digits: Vec<_> = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, ':' | ','))
+        .map(|c| c.to_digit(16).map(|d| d as u8).ok_or_else(|| format!("invalid hex digit: {c:?}")))
+        .collect::<std::result::Result<_, _>>()?;
+    if digits.len() % 2 != 0 {
+        return Err("odd number of hex digits".into());
+    }
+    Ok(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Format::Hex),
+            "binary" => Ok(Format::Binary),
+            _ => Err(format!("unknown format: {s}")),
+        }
+    }
+}
+
+impl FromStr for Contents {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "packets" => Ok(Contents::Packets),
+            "message" => Ok(Contents::Message),
+            "recording" => Ok(Contents::Recording),
+            _ => Err(format!("unknown type: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        time::{Duration, SystemTime},
+    };
+
+    use pretty_assertions::assert_eq;
+    use serde_vici::{
+        packet::Packet,
+        record::{Direction, Frame},
+    };
+
+    use super::*;
+
+    fn dump(args: &[&str], input: &[u8]) -> (String, Result<()>) {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut out = vec![];
+        let result = run(&args, &mut &input[..], &mut out);
+        (String::from_utf8(out).unwrap(), result)
+    }
+
+    #[test]
+    fn dump_hex() {
+        let input = b"00 00 00 14 00 07 76 65 72 73 69 6f 6e\n0x03 0x02 0x69 0x64 0x00 0x05 0x67 0x77 0x2d 0x30";
+
+        let (actual, result) = dump(&[], input);
+        assert_eq!(
+            actual,
+            concat!(
+                "00000000  00 00 00 14                 length = 20\n",
+                "00000004  00                          packet type = CMD_REQUEST\n",
+                "00000005  07 76 65 72 73 69 6f 6e     name (7) = \"version\"\n",
+                "0000000d  03                          KEY_VALUE\n",
+                "0000000e  02 69 64                    key (2) = \"id\"\n",
+                "00000011  00                          ^ EOF while parsing value at position 17\n",
+            )
+        );
+        assert_eq!(result.unwrap_err().to_string(), "the input is malformed");
+
+        let (_, result) = dump(&["-f", "hex"], b"00 0");
+        assert_eq!(result.unwrap_err().to_string(), "odd number of hex digits");
+    }
+
+    #[test]
+    fn dump_binary() {
+        #[rustfmt::skip]
+        let input = [
+            // key1 = value1
+            3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+        ];
+
+        let (actual, result) = dump(&["--type", "message"], &input);
+        assert_eq!(
+            actual,
+            concat!(
+                "00000000  03                          KEY_VALUE\n",
+                "00000001  04 6b 65 79 31              key (4) = \"key1\"\n",
+                "00000006  00 06 76 61 6c 75 65 31     value (6) = \"value1\"\n",
+            )
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn dump_recording() {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_micros(1_650_000_000_000_001);
+        let recording = Recording {
+            frames: vec![
+                Frame::new(Direction::Sent, timestamp, Packet::request("version", &()).unwrap()),
+                Frame::new(
+                    Direction::Received,
+                    timestamp,
+                    Packet::response(&BTreeMap::from([("daemon", "charon")])).unwrap(),
+                ),
+            ],
+        };
+        let input = serde_vici::to_vec(&recording).unwrap();
+
+        let (actual, result) = dump(&["-f", "binary", "-t", "recording"], &input);
+        assert_eq!(
+            actual,
+            concat!(
+                "frame 0: Sent CMD_REQUEST version at 1650000000.000001\n",
+                "frame 1: Received CMD_RESPONSE at 1650000000.000001\n",
+                "00000000  03                          KEY_VALUE\n",
+                "00000001  06 64 61 65 6d 6f 6e        key (6) = \"daemon\"\n",
+                "00000008  00 06 63 68 61 72 6f 6e     value (6) = \"charon\"\n",
+            )
+        );
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,551 @@
+//! Send commands to `charon` and listen for its events over the VICI socket.
+
+use std::{
+    env, error,
+    fmt::Write as _,
+    io::{self, Read, Write},
+    process::ExitCode,
+    str::{self, FromStr},
+    sync::mpsc,
+    thread,
+};
+
+use serde_vici::{
+    client::{Backoff, Client, Notification, SharedClient},
+    swanctl::{Section, Value},
+    transport::Endpoint,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error + Send + Sync>>;
+
+const USAGE: &str = "\
+Usage: vici [OPTIONS] <COMMAND> [BODY]
+       vici [OPTIONS] listen <EVENT>...
+
+Sends COMMAND with BODY as its message and prints the response, or listens for events.
+BODY is read from the standard input if it is `-`.
+
+Options:
+  -u, --uri <URI>        The URI of the VICI socket [default: unix:///var/run/charon.vici]
+  -i, --input <FORMAT>   The format of BODY: json, raw [default: json]
+  -o, --output <FORMAT>  The format of the output: json, pretty, raw [default: pretty]
+  -s, --stream <EVENT>   Prints the events of EVENT streamed by COMMAND before its response
+  -n, --count <N>        Exits after receiving N events when listening
+  -h, --help             Prints this help
+";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Json,
+    Pretty,
+    Raw,
+}
+
+struct Args {
+    endpoint: Endpoint,
+    input: Format,
+    output: Format,
+    stream: Option<String>,
+    count: Option<usize>,
+    positional: Vec<String>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<_> = env::args().skip(1).collect();
+    match run(&args, &mut io::stdin().lock(), &mut io::stdout().lock()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("vici: {e}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run(args: &[String], stdin: &mut impl Read, out: &mut impl Write) -> Result<()> {
+    let Some(args) = parse_args(args)? else {
+        out.write_all(USAGE.as_bytes())?;
+        return Ok(());
+    };
+
+    let Some((command, rest)) = args.positional.split_first() else {
+        return Err(format!("missing command\n\n{USAGE}").into());
+    };
+    if command == "listen" {
+        return listen(&args, rest, out);
+    }
+
+    let body = match rest {
+        [] => Section::new(),
+        [body] if body == "-" => {
+            let mut input = String::new();
+            stdin.read_to_string(&mut input)?;
+            parse_body(args.input, &input)?
+        },
+        [body] => parse_body(args.input, body)?,
+        _ => return Err(format!("unexpected argument: {}", rest[1]).into()),
+    };
+
+    let mut client = Client::connect(&args.endpoint)?;
+    let response: Section = match &args.stream {
+        Some(event) => {
+            let mut events = client.stream::<Section, _>(command, event, &body)?;
+            for message in &mut events {
+                print(out, args.output, event, "event", &message?)?;
+            }
+            events.finish()?
+        },
+        None => client.request(command, &body)?,
+    };
+    print(out, args.output, command, "reply", &response)
+}
+
+fn listen(args: &Args, events: &[String], out: &mut impl Write) -> Result<()> {
+    if events.is_empty() {
+        return Err("missing event to listen for".into());
+    }
+
+    let client = SharedClient::connect_with_backoff(&args.endpoint, Backoff::default())?;
+    let (tx, rx) = mpsc::channel();
+    for event in events {
+        let subscription = client.subscribe::<Section>(event)?;
+        let (tx, event) = (tx.clone(), event.clone());
+        thread::spawn(move || {
+            for notification in subscription {
+                if tx.send((event.clone(), notification)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let mut received = 0;
+    while args.count.is_none_or(|count| received < count) {
+        // The subscriptions end when the client gives up reconnecting.
+        let Ok((event, notification)) = rx.recv() else {
+            return Err("connection closed".into());
+        };
+        match notification? {
+            Notification::Event(message) => {
+                print(out, args.output, &event, "event", &message)?;
+                out.flush()?;
+                received += 1;
+            },
+            Notification::Reconnected => eprintln!("vici: reconnected"),
+        }
+    }
+    Ok(())
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Args>> {
+    let mut parsed = Args {
+        endpoint: Endpoint::default(),
+        input: Format::Json,
+        output: Format::Pretty,
+        stream: None,
+        count: None,
+        positional: vec![],
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {arg}"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-u" | "--uri" => parsed.endpoint = value()?.parse()?,
+            "-i" | "--input" => parsed.input = value()?.parse()?,
+            "-o" | "--output" => parsed.output = value()?.parse()?,
+            "-s" | "--stream" => parsed.stream = Some(value()?.clone()),
+            "-n" | "--count" => parsed.count = Some(value()?.parse()?),
+            "-" => parsed.positional.push(arg.clone()),
+            option if option.starts_with('-') => return Err(format!("unknown option: {option}").into()),
+            _ => parsed.positional.push(arg.clone()),
+        }
+    }
+    Ok(Some(parsed))
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "pretty" => Ok(Format::Pretty),
+            "raw" => Ok(Format::Raw),
+            _ => Err(format!("unknown format: {s}")),
+        }
+    }
+}
+
+fn parse_body(format: Format, input: &str) -> Result<Section> {
+    match format {
+        Format::Json => {
+            let json: serde_json::Value = serde_json::from_str(input)?;
+            if !json.is_object() {
+                return Err("the body must be a JSON object".into());
+            }
+            Ok(serde_vici::from_slice(&serde_vici::to_vec(&json)?)?)
+        },
+        Format::Raw => RawParser::new(input).parse(),
+        Format::Pretty => Err("the body cannot be given in the pretty format".into()),
+    }
+}
+
+fn print(out: &mut impl Write, format: Format, name: &str, kind: &str, message: &Section) -> Result<()> {
+    let mut output = String::new();
+    match format {
+        Format::Json if kind == "event" => {
+            let json = serde_json::json!({ "event": name, "message": to_json_section(message) });
+            let _ = writeln!(output, "{json}");
+        },
+        Format::Json => {
+            let _ = writeln!(output, "{}", to_json_section(message));
+        },
+        Format::Pretty => {
+            let _ = writeln!(output, "{name} {kind} {{");
+            write_pretty(&mut output, message, 1);
+            output.push_str("}\n");
+        },
+        Format::Raw => {
+            let _ = write!(output, "{name} {kind} ");
+            write_raw(&mut output, message);
+            output.push('\n');
+        },
+    }
+    out.write_all(output.as_bytes())?;
+    Ok(())
+}
+
+fn to_json_section(section: &Section) -> serde_json::Value {
+    let object = section.iter().map(|(key, value)| (key.to_string(), to_json(value))).collect();
+    serde_json::Value::Object(object)
+}
+
+fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Bytes(v) => serde_json::Value::String(String::from_utf8_lossy(v).into_owned()),
+        Value::List(items) => items
+            .iter()
+            .map(|item| serde_json::Value::String(String::from_utf8_lossy(item).into_owned()))
+            .collect(),
+        Value::Section(section) => to_json_section(section),
+    }
+}
+
+/// Writes the section indented as `swanctl --pretty` does.
+fn write_pretty(output: &mut String, section: &Section, depth: usize) {
+    let indent = "  ".repeat(depth);
+    for (key, value) in section.iter() {
+        match value {
+            Value::Bytes(v) => {
+                let _ = writeln!(output, "{indent}{key} = {}", display(v));
+            },
+            Value::List(items) => {
+                let _ = writeln!(output, "{indent}{key} = [");
+                for item in items {
+                    let _ = writeln!(output, "{indent}  {}", display(item));
+                }
+                let _ = writeln!(output, "{indent}]");
+            },
+            Value::Section(v) => {
+                let _ = writeln!(output, "{indent}{key} {{");
+                write_pretty(output, v, depth + 1);
+                let _ = writeln!(output, "{indent}}}");
+            },
+        }
+    }
+}
+
+/// Writes the section on a single line as `swanctl --raw` does, quoting values so that they can be parsed back.
+fn write_raw(output: &mut String, section: &Section) {
+    output.push('{');
+    for (i, (key, value)) in section.iter().enumerate() {
+        if i > 0 {
+            output.push(' ');
+        }
+        match value {
+            Value::Bytes(v) => {
+                let _ = write!(output, "{key}={}", quote(&display(v)));
+            },
+            Value::List(items) => {
+                let items: Vec<_> = items.iter().map(|item| quote(&display(item))).collect();
+                let _ = write!(output, "{key}=[{}]", items.join(" "));
+            },
+            Value::Section(v) => {
+                let _ = write!(output, "{key} ");
+                write_raw(output, v);
+            },
+        }
+    }
+    output.push('}');
+}
+
+/// Returns the value as text, or as hex prefixed with `0x` if it is binary.
+fn display(value: &[u8]) -> String {
+    match str::from_utf8(value) {
+        Ok(s) if !s.contains(char::is_control) => s.to_string(),
+        _ => value.iter().fold("0x".to_string(), |mut hex, b| {
+            let _ = write!(hex, "{b:02x}");
+            hex
+        }),
+    }
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || is_delimiter(c)) {
+        return value.to_string();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn is_delimiter(c: char) -> bool {
+    matches!(c, '{' | '}' | '[' | ']' | '=' | '"')
+}
+
+/// Parses a message in the format written by [`write_raw`], with or without the outermost braces.
+struct RawParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> RawParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<Section> {
+        self.skip_whitespace();
+        let nested = self.eat('{');
+        let section = self.parse_section(nested)?;
+
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(section),
+            Some(c) => Err(self.error(&format!("unexpected '{c}'"))),
+        }
+    }
+
+    fn parse_section(&mut self, nested: bool) -> Result<Section> {
+        let mut section = Section::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None if nested => return Err(self.error("missing '}'")),
+                None => return Ok(section),
+                Some('}') if nested => {
+                    self.pos += 1;
+                    return Ok(section);
+                },
+                Some(_) => {},
+            }
+
+            let name = self.parse_word()?;
+            self.skip_whitespace();
+            if self.eat('{') {
+                let child = self.parse_section(true)?;
+                section.insert(name, child);
+            } else if self.eat('=') {
+                self.skip_whitespace();
+                if self.eat('[') {
+                    let mut items = vec![];
+                    loop {
+                        self.skip_whitespace();
+                        if self.eat(']') {
+                            break;
+                        }
+                        items.push(self.parse_word()?.into_bytes());
+                    }
+                    section.insert(name, Value::List(items));
+                } else {
+                    let value = self.parse_word()?;
+                    section.insert(name, value);
+                }
+            } else {
+                return Err(self.error(&format!("expected '=' or '{{' after '{name}'")));
+            }
+        }
+    }
+
+    fn parse_word(&mut self) -> Result<String> {
+        if self.eat('"') {
+            let mut word = String::new();
+            let mut chars = self.input[self.pos..].char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.pos += i + 1;
+                        return Ok(word);
+                    },
+                    '\\' => word.extend(chars.next().map(|(_, c)| c)),
+                    c => word.push(c),
+                }
+            }
+            return Err(self.error("missing '\"'"));
+        }
+
+        let rest = &self.input[self.pos..];
+        let len = rest.find(|c: char| c.is_whitespace() || is_delimiter(c)).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name or value"));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, msg: &str) -> Box<dyn error::Error + Send + Sync> {
+        format!("{msg} at offset {} of the body", self.pos).into()
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::{collections::BTreeMap, time::Duration};
+
+    use pretty_assertions::assert_eq;
+    use serde_vici::testing::{MockServer, Reply};
+
+    use super::*;
+
+    fn vici(server: &MockServer, args: &[&str]) -> Result<String> {
+        let mut args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        args.splice(0..0, ["--uri".to_string(), server.endpoint().to_string()]);
+
+        let mut out = vec![];
+        run(&args, &mut &b"{\"ike\": \"gw\"}"[..], &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn server() -> MockServer {
+        let server = MockServer::bind_tcp().unwrap();
+        server.handle("version", |_| {
+            Reply::new(&BTreeMap::from([("daemon", "charon"), ("version", "5.9.5")]))
+        });
+        server.handle("load-conn", |_| Reply::new(&BTreeMap::from([("success", "yes")])));
+        server.handle("terminate", |request| {
+            let ike: BTreeMap<String, String> = request.deserialize()?;
+            Reply::new(&BTreeMap::from([
+                ("success", "no"),
+                ("errmsg", &*format!("no SA named {}", ike["ike"])),
+            ]))
+        });
+        server.handle("list-sas", |_| {
+            Reply::new(&())?
+                .with_event("list-sa", &BTreeMap::from([("gw-01", BTreeMap::from([("state", "ESTABLISHED")]))]))?
+                .with_event("list-sa", &BTreeMap::from([("gw-02", BTreeMap::from([("state", "CONNECTING")]))]))
+        });
+        server
+    }
+
+    #[test]
+    fn request() {
+        let server = server();
+
+        let actual = vici(&server, &["version"]).unwrap();
+        assert_eq!(actual, "version reply {\n  daemon = charon\n  version = 5.9.5\n}\n");
+
+        let actual = vici(&server, &["-o", "json", "version"]).unwrap();
+        assert_eq!(actual, "{\"daemon\":\"charon\",\"version\":\"5.9.5\"}\n");
+
+        let err = vici(&server, &["terminate", "-"]).unwrap_err();
+        assert_eq!(err.to_string(), "command failed: no SA named gw");
+
+        let err = vici(&server, &["stats"]).unwrap_err();
+        assert_eq!(err.to_string(), "unknown command: stats");
+    }
+
+    #[test]
+    fn request_raw() {
+        let server = server();
+
+        let body = r#"{gw {remote_addrs=[192.0.2.1 192.0.2.2] version=2 local {id="C=JP, CN=gw"}}}"#;
+        let actual = vici(&server, &["-i", "raw", "-o", "raw", "load-conn", body]).unwrap();
+        assert_eq!(actual, "load-conn reply {success=yes}\n");
+
+        let local: Section = [("id", "C=JP, CN=gw")].into_iter().collect();
+        let gw: Section = [
+            ("remote_addrs", Value::List(vec![b"192.0.2.1".to_vec(), b"192.0.2.2".to_vec()])),
+            ("version", Value::from("2")),
+            ("local", Value::from(local)),
+        ]
+        .into_iter()
+        .collect();
+        let expected: Section = [("gw", gw)].into_iter().collect();
+        assert_eq!(server.requests()[0].deserialize::<Section>().unwrap(), expected);
+
+        let mut raw = String::new();
+        write_raw(&mut raw, &expected);
+        assert_eq!(raw, body);
+        assert_eq!(RawParser::new(&raw).parse().unwrap(), expected);
+
+        let err = vici(&server, &["-i", "raw", "load-conn", "gw {version"]).unwrap_err();
+        assert_eq!(err.to_string(), "expected '=' or '{' after 'version' at offset 11 of the body");
+    }
+
+    #[test]
+    fn stream() {
+        let server = server();
+
+        let actual = vici(&server, &["-o", "json", "-s", "list-sa", "list-sas"]).unwrap();
+        assert_eq!(
+            actual,
+            concat!(
+                "{\"event\":\"list-sa\",\"message\":{\"gw-01\":{\"state\":\"ESTABLISHED\"}}}\n",
+                "{\"event\":\"list-sa\",\"message\":{\"gw-02\":{\"state\":\"CONNECTING\"}}}\n",
+                "{}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn listen() {
+        let server = server();
+        let endpoint = server.endpoint().to_string();
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let args: Vec<_> = ["--uri", &endpoint, "-o", "raw", "-n", "2", "listen", "log"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect();
+            let mut out = vec![];
+            tx.send(run(&args, &mut io::empty(), &mut out).map(|()| out)).unwrap();
+        });
+
+        let out = loop {
+            server.emit("log", &BTreeMap::from([("msg", "hello world")])).unwrap();
+            if let Ok(out) = rx.recv_timeout(Duration::from_millis(10)) {
+                break String::from_utf8(out.unwrap()).unwrap();
+            }
+        };
+        assert_eq!(out, "log event {msg=\"hello world\"}\nlog event {msg=\"hello world\"}\n");
+    }
+}
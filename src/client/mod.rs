@@ -0,0 +1,552 @@
+//! Send commands to `charon` over the VICI socket.
+
+use std::{io, marker::PhantomData};
+
+use serde::{de, ser};
+
+use crate::{
+    command::CommandResult,
+    error::{Error, ErrorCode, Result},
+    packet::{read_packet, write_packet, Packet, PacketType},
+    transport::{Endpoint, Stream},
+};
+
+#[doc(inline)]
+pub use self::shared::{Backoff, Notification, SharedClient, SharedStreamed, Subscription};
+
+mod shared;
+
+/// A client issuing commands on a single connection to the VICI socket.
+///
+/// # Example
+///
+/// ```no_run
+/// use anyhow::Result;
+/// use serde::Deserialize;
+/// use serde_vici::client::Client;
+///
+/// #[derive(Deserialize)]
+/// struct Version {
+///     daemon: String,
+///     version: String,
+/// }
+///
+/// fn main() -> Result<()> {
+///     let mut client = Client::connect(&"unix:///var/run/charon.vici".parse()?)?;
+///     let version: Version = client.request("version", &())?;
+///     println!("{} {}", version.daemon, version.version);
+///     Ok(())
+/// }
+/// ```
+pub struct Client<S = Stream> {
+    stream: S,
+}
+
+impl Client<Stream> {
+    /// Opens a connection to the VICI socket at the given endpoint.
+    ///
+    /// # Errors
+    /// Connecting can fail if the socket does not exist or refuses the connection.
+    pub fn connect(endpoint: &Endpoint) -> Result<Self> {
+        let stream = Stream::connect(endpoint)?;
+        Ok(Self::new(stream))
+    }
+
+    /// Opens a connection to the VICI socket `charon` listens on by default.
+    ///
+    /// # Errors
+    /// Connecting can fail if the socket does not exist or refuses the connection.
+    pub fn connect_default() -> Result<Self> {
+        Self::connect(&Endpoint::default())
+    }
+}
+
+impl<S> Client<S>
+where
+    S: io::Read + io::Write,
+{
+    /// Creates a client on an already connected stream.
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+
+    /// Gets a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Gets a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Unwraps this client, returning the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Sends a command with the given request message and deserializes its response.
+    ///
+    /// # Errors
+    /// The request can fail if the connection fails, `charon` does not know the command, the response reports `success = no`, or
+    /// the response does not match the structure expected by `T`.
+    pub fn request<T, R>(&mut self, command: &str, request: &R) -> Result<T>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        write_packet(&mut self.stream, &Packet::request(command, request)?)?;
+        let packet = self.read_response(command)?;
+        packet.deserialize::<CommandResult<T>>()?.into_result()
+    }
+
+    /// Sends a command whose results are streamed as events before its response.
+    ///
+    /// The client registers for `event` before sending the command, and the returned [`Streamed`] yields each of these events
+    /// deserialized as `T` until the response arrives. The event is unregistered when the [`Streamed`] is finished or dropped, even
+    /// if reading the events failed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use anyhow::Result;
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_vici::client::Client;
+    /// use std::collections::HashMap;
+    ///
+    /// #[derive(Serialize)]
+    /// struct ListSas {
+    ///     noblock: bool,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Sa {
+    ///     state: String,
+    /// }
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut client = Client::connect_default()?;
+    ///     let mut sas = client.stream::<HashMap<String, Sa>, _>("list-sas", "list-sa", &ListSas { noblock: true })?;
+    ///     for sa in &mut sas {
+    ///         for (name, sa) in sa? {
+    ///             println!("{name}: {}", sa.state);
+    ///         }
+    ///     }
+    ///     sas.finish::<()>()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// The request can fail if the connection fails, or `charon` does not know the event.
+    pub fn stream<T, R>(&mut self, command: &str, event: &str, request: &R) -> Result<Streamed<'_, S, T>>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        let request = Packet::request(command, request)?;
+        self.register(event)?;
+
+        let mut streamed = Streamed {
+            client: self,
+            command: command.to_string(),
+            event: event.to_string(),
+            state: StreamState::Registered,
+            marker: PhantomData,
+        };
+        write_packet(&mut streamed.client.stream, &request)?;
+        streamed.state = StreamState::Streaming;
+        Ok(streamed)
+    }
+
+    fn register(&mut self, event: &str) -> Result<()> {
+        self.confirm(PacketType::EventRegister, event)
+    }
+
+    fn unregister(&mut self, event: &str) -> Result<()> {
+        self.confirm(PacketType::EventUnregister, event)
+    }
+
+    fn confirm(&mut self, packet_type: PacketType, event: &str) -> Result<()> {
+        write_packet(&mut self.stream, &Packet::new(packet_type, Some(event.to_string()), vec![]))?;
+        loop {
+            let packet = read_packet(&mut self.stream)?;
+            if packet.packet_type != PacketType::Event {
+                return check_confirm(event, packet);
+            }
+        }
+    }
+
+    fn read_response(&mut self, command: &str) -> Result<Packet> {
+        loop {
+            let packet = read_packet(&mut self.stream)?;
+            // Events are not subscribed on this client and thus can be safely skipped.
+            if packet.packet_type != PacketType::Event {
+                return check_response(command, packet);
+            }
+        }
+    }
+}
+
+/// Checks that the packet is the response to the given command.
+fn check_response(command: &str, packet: Packet) -> Result<Packet> {
+    match packet.packet_type {
+        PacketType::CmdResponse => Ok(packet),
+        PacketType::CmdUnknown => Err(Error::data(ErrorCode::Message(format!("unknown command: {command}")), None, None)),
+        t => Err(Error::data(
+            ErrorCode::Message("unexpected packet type".into()),
+            Some(t as u8),
+            None,
+        )),
+    }
+}
+
+/// Checks that the packet confirms the registration or deregistration of the given event.
+fn check_confirm(event: &str, packet: Packet) -> Result<()> {
+    match packet.packet_type {
+        PacketType::EventConfirm => Ok(()),
+        PacketType::EventUnknown => Err(Error::data(ErrorCode::Message(format!("unknown event: {event}")), None, None)),
+        t => Err(Error::data(
+            ErrorCode::Message("unexpected packet type".into()),
+            Some(t as u8),
+            None,
+        )),
+    }
+}
+
+/// An iterator over the events streamed by a command, created by [`Client::stream`].
+pub struct Streamed<'a, S, T>
+where
+    S: io::Read + io::Write,
+{
+    client: &'a mut Client<S>,
+    command: String,
+    event: String,
+    state: StreamState,
+    marker: PhantomData<T>,
+}
+
+enum StreamState {
+    Registered,
+    Streaming,
+    Responded(Packet),
+    Closed,
+}
+
+impl<S, T> Streamed<'_, S, T>
+where
+    S: io::Read + io::Write,
+    T: de::DeserializeOwned,
+{
+    /// Skips the remaining events, unregisters the event, and deserializes the response of the command.
+    ///
+    /// # Errors
+    /// Finishing can fail if the connection fails, `charon` does not know the command, the response reports `success = no`, or the
+    /// response does not match the structure expected by `R`.
+    pub fn finish<R>(mut self) -> Result<R>
+    where
+        R: de::DeserializeOwned,
+    {
+        let response = self.close()?;
+        response.deserialize::<CommandResult<R>>()?.into_result()
+    }
+
+    fn read_event(&mut self) -> Result<Option<Packet>> {
+        loop {
+            let packet = read_packet(&mut self.client.stream)?;
+            match packet.packet_type {
+                PacketType::Event if packet.name.as_deref() == Some(&self.event) => return Ok(Some(packet)),
+                PacketType::Event => {},
+                PacketType::CmdResponse => {
+                    self.state = StreamState::Responded(packet);
+                    return Ok(None);
+                },
+                PacketType::CmdUnknown => {
+                    self.state = StreamState::Closed;
+                    let _ = self.client.unregister(&self.event);
+                    return Err(Error::data(
+                        ErrorCode::Message(format!("unknown command: {}", self.command)),
+                        None,
+                        None,
+                    ));
+                },
+                t => {
+                    return Err(Error::data(
+                        ErrorCode::Message("unexpected packet type".into()),
+                        Some(t as u8),
+                        None,
+                    ));
+                },
+            }
+        }
+    }
+}
+
+impl<S, T> Streamed<'_, S, T>
+where
+    S: io::Read + io::Write,
+{
+    fn close(&mut self) -> Result<Packet> {
+        let response = match std::mem::replace(&mut self.state, StreamState::Closed) {
+            StreamState::Streaming => self.client.read_response(&self.command),
+            StreamState::Responded(response) => Ok(response),
+            StreamState::Registered | StreamState::Closed => {
+                return Err(Error::data(
+                    ErrorCode::Message(format!("stream closed: {}", self.command)),
+                    None,
+                    None,
+                ));
+            },
+        };
+
+        // The event is unregistered regardless of the response, unless the connection itself is broken.
+        match response {
+            Ok(response) => {
+                self.client.unregister(&self.event)?;
+                Ok(response)
+            },
+            Err(e) if e.is_io() || e.is_eof() => Err(e),
+            Err(e) => {
+                let _ = self.client.unregister(&self.event);
+                Err(e)
+            },
+        }
+    }
+}
+
+impl<S, T> Iterator for Streamed<'_, S, T>
+where
+    S: io::Read + io::Write,
+    T: de::DeserializeOwned,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !matches!(self.state, StreamState::Streaming) {
+            return None;
+        }
+
+        match self.read_event() {
+            Ok(event) => event.map(|event| event.deserialize()),
+            Err(e) => {
+                // A broken connection cannot yield further events.
+                if e.is_io() || e.is_eof() {
+                    self.state = StreamState::Closed;
+                }
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+impl<S, T> Drop for Streamed<'_, S, T>
+where
+    S: io::Read + io::Write,
+{
+    fn drop(&mut self) {
+        match std::mem::replace(&mut self.state, StreamState::Closed) {
+            StreamState::Closed => {},
+            // The command was never sent, so there is no response to wait for.
+            StreamState::Registered => {
+                let _ = self.client.unregister(&self.event);
+            },
+            state => {
+                self.state = state;
+                let _ = self.close();
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, time::Duration};
+
+    use pretty_assertions::assert_eq;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::testing::{MockServer, Reply};
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct Version {
+        pub daemon: String,
+        pub version: String,
+    }
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    pub(super) struct Sa {
+        pub state: String,
+    }
+
+    /// Answers the commands the tests issue the way `charon` would.
+    pub(super) fn charon(server: MockServer) -> MockServer {
+        let server = server.with_events(["list-sa", "log"]);
+        server.handle("version", |_| {
+            Reply::new(&Version {
+                daemon: "charon".to_string(),
+                version: "5.9.5".to_string(),
+            })
+        });
+        server.handle("list-sas", |_| {
+            let mut reply = Reply::new(&())?;
+            for (name, state) in [("gw-01", "ESTABLISHED"), ("gw-02", "CONNECTING")] {
+                let sa = BTreeMap::from([(name, Sa { state: state.to_string() })]);
+                reply = reply.with_event("list-sa", &sa)?.with_event("log", &())?;
+            }
+            Ok(reply)
+        });
+        server.handle("sleep", |_| {
+            Ok(Reply::new(&())?.with_event("log", &())?.with_delay(Duration::from_millis(100)))
+        });
+        server.handle("terminate", |_| {
+            Reply::new(&BTreeMap::from([
+                ("success", "no"),
+                ("errmsg", "no matching SAs to terminate found"),
+            ]))
+        });
+        server
+    }
+
+    fn connect() -> (MockServer, Client) {
+        let server = charon(MockServer::bind_tcp().unwrap());
+        let client = Client::connect(server.endpoint()).unwrap();
+        (server, client)
+    }
+
+    /// A stream that fails to send commands while passing other packets.
+    struct Unsendable {
+        input: io::Cursor<Vec<u8>>,
+        sent: Vec<u8>,
+    }
+
+    impl io::Read for Unsendable {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl io::Write for Unsendable {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.get(4) == Some(&(PacketType::CmdRequest as u8)) {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.sent.extend(buf.get(4));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn request_tcp() {
+        let (_server, mut client) = connect();
+
+        let actual: Version = client.request("version", &()).unwrap();
+        assert_eq!(
+            actual,
+            Version {
+                daemon: "charon".to_string(),
+                version: "5.9.5".to_string(),
+            }
+        );
+
+        let err = client.request::<Version, _>("stats", &()).unwrap_err();
+        assert_eq!(err.to_string(), "unknown command: stats");
+
+        let err = client.request::<(), _>("terminate", &BTreeMap::from([("ike", "gw")])).unwrap_err();
+        assert!(err.is_command());
+        assert_eq!(err.to_string(), "command failed: no matching SAs to terminate found");
+    }
+
+    #[test]
+    fn stream_events() {
+        let (server, mut client) = connect();
+
+        let sas = client.stream::<BTreeMap<String, Sa>, _>("list-sas", "list-sa", &()).unwrap();
+        let actual: Vec<_> = sas.map(|sa| sa.unwrap()).collect();
+        assert_eq!(
+            actual,
+            vec![
+                BTreeMap::from([(
+                    "gw-01".to_string(),
+                    Sa {
+                        state: "ESTABLISHED".to_string(),
+                    },
+                )]),
+                BTreeMap::from([(
+                    "gw-02".to_string(),
+                    Sa {
+                        state: "CONNECTING".to_string(),
+                    },
+                )]),
+            ]
+        );
+        assert_eq!(server.registered(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn stream_finish() {
+        let (server, mut client) = connect();
+
+        let mut sas = client.stream::<BTreeMap<String, Sa>, _>("list-sas", "list-sa", &()).unwrap();
+        assert!(sas.next().unwrap().is_ok());
+        sas.finish::<()>().unwrap();
+        assert_eq!(server.registered(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn stream_error() {
+        let (server, mut client) = connect();
+
+        // Events that cannot be deserialized do not prevent the event from being unregistered.
+        let mut sas = client.stream::<Sa, _>("list-sas", "list-sa", &()).unwrap();
+        assert!(sas.next().unwrap().unwrap_err().is_data());
+        drop(sas);
+        assert_eq!(server.registered(), Vec::<String>::new());
+
+        let mut sas = client.stream::<Sa, _>("list-conns", "list-sa", &()).unwrap();
+        assert_eq!(sas.next().unwrap().unwrap_err().to_string(), "unknown command: list-conns");
+        assert!(sas.next().is_none());
+        drop(sas);
+        assert_eq!(server.registered(), Vec::<String>::new());
+
+        let err = client.stream::<Sa, _>("list-conns", "list-conn", &()).err().unwrap();
+        assert_eq!(err.to_string(), "unknown event: list-conn");
+    }
+
+    #[test]
+    fn stream_unsent() {
+        let mut input = vec![];
+        let confirm = Packet::new(PacketType::EventConfirm, None, vec![]);
+        write_packet(&mut input, &confirm).unwrap();
+        write_packet(&mut input, &confirm).unwrap();
+        let len = input.len() as u64;
+
+        // A command that cannot be sent has no response, so the event is unregistered without waiting for one.
+        let mut client = Client::new(Unsendable {
+            input: io::Cursor::new(input),
+            sent: vec![],
+        });
+        let err = client.stream::<Sa, _>("list-sas", "list-sa", &()).err().unwrap();
+        assert!(err.is_io());
+        assert_eq!(
+            client.get_ref().sent,
+            vec![PacketType::EventRegister as u8, PacketType::EventUnregister as u8]
+        );
+        assert_eq!(client.get_ref().input.position(), len);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn request_unix() {
+        let path = std::env::temp_dir().join(format!("serde-vici-client-{}.sock", std::process::id()));
+        let server = charon(MockServer::bind_unix(&path).unwrap());
+
+        let mut client = Client::connect(server.endpoint()).unwrap();
+        let actual: Version = client.request("version", &()).unwrap();
+        assert_eq!(actual.daemon, "charon");
+    }
+}
@@ -0,0 +1,1037 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io, iter,
+    marker::PhantomData,
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{de, ser};
+
+use super::{check_confirm, check_response, StreamState};
+use crate::{
+    command::CommandResult,
+    error::{Error, ErrorCode, Result},
+    packet::{read_packet, write_packet, Packet, PacketType},
+    transport::{Endpoint, Stream},
+};
+
+/// A client that can be cloned and shared across threads, issuing commands on a single connection to the VICI socket.
+///
+/// The connection is owned by a background thread that sends the commands one at a time in the order they are issued, so that the
+/// events streamed by a command are never mixed with those of another command. Events arriving outside of a streamed command are
+/// delivered to the [`Subscription`]s for them. The connection is closed once every clone and subscription is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::thread;
+///
+/// use anyhow::Result;
+/// use serde::Deserialize;
+/// use serde_vici::client::SharedClient;
+///
+/// #[derive(Deserialize)]
+/// struct Version {
+///     daemon: String,
+///     version: String,
+/// }
+///
+/// fn main() -> Result<()> {
+///     let client = SharedClient::connect_default()?;
+///     let workers: Vec<_> = (0..4)
+///         .map(|_| {
+///             let client = client.clone();
+///             thread::spawn(move || client.request::<Version, _>("version", &()))
+///         })
+///         .collect();
+///
+///     for worker in workers {
+///         let version = worker.join().unwrap()?;
+///         println!("{} {}", version.daemon, version.version);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct SharedClient {
+    inner: Arc<Inner>,
+    timeout: Option<Duration>,
+}
+
+/// An iterator over the events streamed by a command, created by [`SharedClient::stream`].
+pub struct SharedStreamed<T> {
+    command: String,
+    deadline: Option<Instant>,
+    events: Receiver<Packet>,
+    response: Receiver<Result<Packet>>,
+    state: StreamState,
+    marker: PhantomData<fn() -> T>,
+}
+
+/// An iterator over the events subscribed with [`SharedClient::subscribe`].
+///
+/// The iterator blocks until the next event arrives, and ends when the connection is closed. The event is unregistered when the
+/// subscription is dropped and no other subscription needs it.
+pub struct Subscription<T> {
+    inner: Arc<Inner>,
+    id: u64,
+    events: Receiver<Delivery>,
+    marker: PhantomData<fn() -> T>,
+}
+
+/// An item yielded by a [`Subscription`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Notification<T> {
+    /// An event the subscription is for.
+    Event(T),
+
+    /// The connection was lost and established again, and the event was registered again.
+    ///
+    /// The events raised while the connection was lost are missed.
+    Reconnected,
+}
+
+/// The delays between the attempts to reconnect after the connection is lost, used by [`SharedClient::connect_with_backoff`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    max_attempts: Option<usize>,
+}
+
+struct Inner {
+    tx: Sender<Message>,
+    next_id: AtomicU64,
+}
+
+enum Message {
+    Request {
+        packet: Packet,
+        deadline: Option<Instant>,
+        reply: Sender<Result<Packet>>,
+    },
+    Stream {
+        packet: Packet,
+        deadline: Option<Instant>,
+        event: String,
+        events: Sender<Packet>,
+        reply: Sender<Result<Packet>>,
+    },
+    Subscribe {
+        id: u64,
+        event: String,
+        events: Sender<Delivery>,
+        reply: Sender<Result<()>>,
+    },
+    Unsubscribe {
+        id: u64,
+    },
+    Received(u64, Result<Packet>),
+    Close,
+}
+
+struct Subscriber {
+    id: u64,
+    event: String,
+    events: Sender<Delivery>,
+}
+
+enum Delivery {
+    Event(Packet),
+    Reconnected,
+}
+
+/// The state of the connection, owned by the background thread.
+struct Connection {
+    stream: Option<Stream>,
+    generation: u64,
+    tx: Sender<Message>,
+    inbox: Receiver<Message>,
+    queue: VecDeque<Message>,
+    reconnect: Option<(Endpoint, Backoff)>,
+    subscribers: Vec<Subscriber>,
+    registered: HashSet<String>,
+    streaming: Option<(String, Sender<Packet>)>,
+    cancelled: VecDeque<Cancelled>,
+}
+
+/// A command that timed out after it was sent, whose events and answer are discarded as they arrive.
+struct Cancelled {
+    generation: u64,
+    command: String,
+    event: Option<String>,
+}
+
+impl SharedClient {
+    /// Opens a connection to the VICI socket at the given endpoint.
+    ///
+    /// # Errors
+    /// Connecting can fail if the socket does not exist or refuses the connection.
+    pub fn connect(endpoint: &Endpoint) -> Result<Self> {
+        let stream = Stream::connect(endpoint)?;
+        Self::new(stream)
+    }
+
+    /// Opens a connection to the VICI socket `charon` listens on by default.
+    ///
+    /// # Errors
+    /// Connecting can fail if the socket does not exist or refuses the connection.
+    pub fn connect_default() -> Result<Self> {
+        Self::connect(&Endpoint::default())
+    }
+
+    /// Opens a connection to the VICI socket at the given endpoint, connecting again with the backoff whenever the connection is
+    /// lost.
+    ///
+    /// Once connected again, the events of the active subscriptions are registered again and each subscription yields
+    /// [`Notification::Reconnected`]. Commands in progress when the connection is lost, or issued until it is established again,
+    /// fail. The client is closed if the backoff gives up.
+    ///
+    /// # Errors
+    /// Connecting can fail if the socket does not exist or refuses the first connection.
+    pub fn connect_with_backoff(endpoint: &Endpoint, backoff: Backoff) -> Result<Self> {
+        let stream = Stream::connect(endpoint)?;
+        Self::spawn(stream, Some((endpoint.clone(), backoff)))
+    }
+
+    /// Creates a client on an already connected stream, spawning the threads that own the connection.
+    ///
+    /// # Errors
+    /// Creating the client can fail if the stream cannot be cloned or the threads cannot be spawned.
+    pub fn new(stream: Stream) -> Result<Self> {
+        Self::spawn(stream, None)
+    }
+
+    fn spawn(stream: Stream, reconnect: Option<(Endpoint, Backoff)>) -> Result<Self> {
+        let (tx, inbox) = mpsc::channel();
+        let mut connection = Connection {
+            stream: None,
+            generation: 0,
+            tx: tx.clone(),
+            inbox,
+            queue: VecDeque::new(),
+            reconnect,
+            subscribers: vec![],
+            registered: HashSet::new(),
+            streaming: None,
+            cancelled: VecDeque::new(),
+        };
+        connection.attach(stream)?;
+        thread::Builder::new()
+            .name("vici-connection".to_string())
+            .spawn(move || connection.run())?;
+
+        let next_id = AtomicU64::new(0);
+        let inner = Arc::new(Inner { tx, next_id });
+        let timeout = None;
+        Ok(Self { inner, timeout })
+    }
+
+    /// Sets the timeout of the commands issued by this client and the clones made from it afterwards.
+    ///
+    /// A command that does not complete in time fails with an error of [`Category::Timeout`](crate::error::Category::Timeout).
+    /// `charon` cannot cancel a command, so the connection is kept and the rest of its events and its response are discarded as they
+    /// arrive. The next command is sent right away, but `charon` answers it only once the timed out command completes. Commands
+    /// queued behind it fail with a timeout without being sent if their turn comes too late.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the timeout of the commands issued by this client.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Sends a command with the given request message and deserializes its response.
+    ///
+    /// The command is queued after the commands issued before it, possibly by other clones of this client.
+    ///
+    /// # Errors
+    /// The request can fail if the connection fails, `charon` does not know the command, the response reports `success = no`, the
+    /// response does not match the structure expected by `T`, or the timeout of this client elapses.
+    pub fn request<T, R>(&self, command: &str, request: &R) -> Result<T>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        self.call(command, request, self.timeout)
+    }
+
+    /// Sends a command with the given request message and deserializes its response, failing if it does not complete within the
+    /// given timeout instead of the timeout of this client.
+    ///
+    /// # Errors
+    /// The request can fail if the connection fails, `charon` does not know the command, the response reports `success = no`, the
+    /// response does not match the structure expected by `T`, or the timeout elapses.
+    pub fn request_with_timeout<T, R>(&self, command: &str, request: &R, timeout: Duration) -> Result<T>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        self.call(command, request, Some(timeout))
+    }
+
+    fn call<T, R>(&self, command: &str, request: &R, timeout: Option<Duration>) -> Result<T>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        let packet = Packet::request(command, request)?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let (reply, response) = mpsc::channel();
+        self.inner.send(Message::Request { packet, deadline, reply })?;
+
+        let packet = recv(&response, deadline, command)?.ok_or_else(closed)??;
+        check_response(command, packet)?.deserialize::<CommandResult<T>>()?.into_result()
+    }
+
+    /// Sends a command whose results are streamed as events before its response.
+    ///
+    /// The event is registered just before the command is sent and unregistered once its response arrives, unless a
+    /// [`Subscription`] needs it. Events arriving in the meantime belong to this command only and are not delivered to
+    /// subscriptions. Dropping the returned [`SharedStreamed`] discards the remaining events without blocking.
+    ///
+    /// The timeout of this client applies to the command as a whole, including all of its events.
+    ///
+    /// # Errors
+    /// The request can fail if the connection is closed. Failures to register the event or send the command, and the timeout, are
+    /// reported by the returned [`SharedStreamed`].
+    pub fn stream<T, R>(&self, command: &str, event: &str, request: &R) -> Result<SharedStreamed<T>>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        self.call_stream(command, event, request, self.timeout)
+    }
+
+    /// Sends a command whose results are streamed as events before its response, failing if it does not complete within the
+    /// given timeout instead of the timeout of this client.
+    ///
+    /// # Errors
+    /// The request can fail if the connection is closed. Failures to register the event or send the command, and the timeout, are
+    /// reported by the returned [`SharedStreamed`].
+    pub fn stream_with_timeout<T, R>(&self, command: &str, event: &str, request: &R, timeout: Duration) -> Result<SharedStreamed<T>>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        self.call_stream(command, event, request, Some(timeout))
+    }
+
+    fn call_stream<T, R>(&self, command: &str, event: &str, request: &R, timeout: Option<Duration>) -> Result<SharedStreamed<T>>
+    where
+        T: de::DeserializeOwned,
+        R: ?Sized + ser::Serialize,
+    {
+        let packet = Packet::request(command, request)?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let (events, events_rx) = mpsc::channel();
+        let (reply, response) = mpsc::channel();
+        let event = event.to_string();
+        self.inner.send(Message::Stream {
+            packet,
+            deadline,
+            event,
+            events,
+            reply,
+        })?;
+
+        Ok(SharedStreamed {
+            command: command.to_string(),
+            deadline,
+            events: events_rx,
+            response,
+            state: StreamState::Streaming,
+            marker: PhantomData,
+        })
+    }
+
+    /// Subscribes to the given event, registering it on the connection unless another subscription already did.
+    ///
+    /// # Errors
+    /// Subscribing can fail if the connection fails or `charon` does not know the event.
+    pub fn subscribe<T>(&self, event: &str) -> Result<Subscription<T>>
+    where
+        T: de::DeserializeOwned,
+    {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let (events, events_rx) = mpsc::channel();
+        let (reply, confirmed) = mpsc::channel();
+        let event = event.to_string();
+        self.inner.send(Message::Subscribe { id, event, events, reply })?;
+        confirmed.recv().map_err(|_| closed())??;
+
+        Ok(Subscription {
+            inner: self.inner.clone(),
+            id,
+            events: events_rx,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T> SharedStreamed<T>
+where
+    T: de::DeserializeOwned,
+{
+    /// Skips the remaining events and deserializes the response of the command.
+    ///
+    /// # Errors
+    /// Finishing can fail if the connection fails, `charon` does not know the command or the event, the response reports
+    /// `success = no`, the response does not match the structure expected by `R`, or the timeout elapses.
+    pub fn finish<R>(mut self) -> Result<R>
+    where
+        R: de::DeserializeOwned,
+    {
+        let response = match std::mem::replace(&mut self.state, StreamState::Closed) {
+            StreamState::Streaming => {
+                while recv(&self.events, self.deadline, &self.command)?.is_some() {}
+                self.response()?
+            },
+            StreamState::Responded(response) => response,
+            StreamState::Registered | StreamState::Closed => {
+                return Err(Error::data(
+                    ErrorCode::Message(format!("stream closed: {}", self.command)),
+                    None,
+                    None,
+                ));
+            },
+        };
+        response.deserialize::<CommandResult<R>>()?.into_result()
+    }
+
+    fn response(&self) -> Result<Packet> {
+        let packet = recv(&self.response, self.deadline, &self.command)?.ok_or_else(closed)??;
+        check_response(&self.command, packet)
+    }
+}
+
+impl<T> Iterator for SharedStreamed<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !matches!(self.state, StreamState::Streaming) {
+            return None;
+        }
+
+        // The events end when the response arrives, after which the response reports whether the command failed.
+        match recv(&self.events, self.deadline, &self.command) {
+            Ok(Some(event)) => Some(event.deserialize()),
+            Err(e) => {
+                self.state = StreamState::Closed;
+                Some(Err(e))
+            },
+            Ok(None) => match self.response() {
+                Ok(response) => {
+                    self.state = StreamState::Responded(response);
+                    None
+                },
+                Err(e) => {
+                    self.state = StreamState::Closed;
+                    Some(Err(e))
+                },
+            },
+        }
+    }
+}
+
+impl<T> Iterator for Subscription<T>
+where
+    T: de::DeserializeOwned,
+{
+    type Item = Result<Notification<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.recv().ok().map(|delivery| match delivery {
+            Delivery::Event(event) => event.deserialize().map(Notification::Event),
+            Delivery::Reconnected => Ok(Notification::Reconnected),
+        })
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        let _ = self.inner.send(Message::Unsubscribe { id: self.id });
+    }
+}
+
+impl Inner {
+    fn send(&self, message: Message) -> Result<()> {
+        self.tx.send(message).map_err(|_| closed())
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let _ = self.tx.send(Message::Close);
+    }
+}
+
+impl Connection {
+    fn run(mut self) {
+        while let Some(message) = self.queue.pop_front().or_else(|| self.inbox.recv().ok()) {
+            match message {
+                // Commands whose callers have given up waiting are not sent at all.
+                Message::Request { packet, deadline, reply }
+                | Message::Stream {
+                    packet, deadline, reply, ..
+                } if is_expired(deadline) => {
+                    let _ = reply.send(Err(Error::timeout(packet.name.as_deref().unwrap_or_default())));
+                },
+                Message::Request { packet, deadline, reply } => {
+                    let _ = reply.send(self.exchange(&packet, deadline));
+                },
+                Message::Stream {
+                    packet,
+                    deadline,
+                    event,
+                    events,
+                    reply,
+                } => {
+                    let _ = reply.send(self.stream(&packet, deadline, event, events));
+                },
+                Message::Subscribe { id, event, events, reply } => {
+                    let result = self.register(&event, None);
+                    if result.is_ok() {
+                        self.subscribers.push(Subscriber { id, event, events });
+                    }
+                    let _ = reply.send(result);
+                },
+                Message::Unsubscribe { id } => {
+                    if let Some(i) = self.subscribers.iter().position(|subscriber| subscriber.id == id) {
+                        let subscriber = self.subscribers.remove(i);
+                        let _ = self.release(&subscriber.event, None);
+                    }
+                },
+                Message::Received(generation, received) if generation == self.generation => match received {
+                    Ok(packet) if self.is_cancelled(&packet) => {},
+                    Ok(packet) => self.dispatch(packet),
+                    Err(_) => self.disconnect(),
+                },
+                Message::Received(..) => {},
+                Message::Close => break,
+            }
+
+            if self.stream.is_none() && !self.reconnect() {
+                break;
+            }
+        }
+    }
+
+    /// Sends the packet and waits for the packet answering it until the deadline, dispatching events and queueing messages in the
+    /// meantime.
+    fn exchange(&mut self, packet: &Packet, deadline: Option<Instant>) -> Result<Packet> {
+        let Some(stream) = &mut self.stream else {
+            return Err(closed());
+        };
+        if let Err(e) = write_packet(stream, packet) {
+            self.disconnect();
+            return Err(e);
+        }
+
+        loop {
+            let message = match deadline {
+                Some(deadline) => self.inbox.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => self.inbox.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match message {
+                // Packets read from a previous connection are stale.
+                Ok(Message::Received(generation, _)) if generation != self.generation => {},
+                Ok(Message::Received(_, Ok(packet))) if self.is_cancelled(&packet) => {},
+                Ok(Message::Received(_, Ok(packet))) if packet.packet_type == PacketType::Event => self.dispatch(packet),
+                Ok(Message::Received(_, Ok(packet))) => return Ok(packet),
+                Ok(Message::Received(_, Err(e))) => {
+                    self.disconnect();
+                    return Err(e);
+                },
+                Ok(message) => self.queue.push_back(message),
+                // The response of a command that timed out would arrive in place of that of the next command, so it is discarded
+                // along with the events the command streams.
+                Err(RecvTimeoutError::Timeout) => {
+                    let command = packet.name.clone().unwrap_or_default();
+                    self.cancelled.push_back(Cancelled {
+                        generation: self.generation,
+                        command: command.clone(),
+                        event: self.streaming.as_ref().map(|(event, _)| event.clone()),
+                    });
+                    return Err(Error::timeout(&command));
+                },
+                Err(RecvTimeoutError::Disconnected) => return Err(closed()),
+            }
+        }
+    }
+
+    fn stream(&mut self, packet: &Packet, deadline: Option<Instant>, event: String, events: Sender<Packet>) -> Result<Packet> {
+        self.register(&event, deadline)?;
+
+        self.streaming = Some((event, events));
+        let response = self.exchange(packet, deadline);
+        let (event, _) = self.streaming.take().unwrap();
+
+        // The event is unregistered regardless of the response, unless the connection itself is broken.
+        match response {
+            Ok(response) => {
+                self.release(&event, None)?;
+                Ok(response)
+            },
+            // The deadline has passed if the command timed out, so the event is not waited for either.
+            Err(e) => {
+                let _ = self.release(&event, deadline);
+                Err(e)
+            },
+        }
+    }
+
+    fn register(&mut self, event: &str, deadline: Option<Instant>) -> Result<()> {
+        if self.registered.contains(event) {
+            return Ok(());
+        }
+
+        let packet = self.exchange(&Packet::new(PacketType::EventRegister, Some(event.to_string()), vec![]), deadline)?;
+        check_confirm(event, packet)?;
+        self.registered.insert(event.to_string());
+        Ok(())
+    }
+
+    fn release(&mut self, event: &str, deadline: Option<Instant>) -> Result<()> {
+        if self.subscribers.iter().any(|subscriber| subscriber.event == event) || !self.registered.remove(event) {
+            return Ok(());
+        }
+
+        let packet = self.exchange(&Packet::new(PacketType::EventUnregister, Some(event.to_string()), vec![]), deadline)?;
+        check_confirm(event, packet)
+    }
+
+    /// Returns true if the packet is an event streamed by a command that timed out, or the answer to the oldest such command, which
+    /// is no longer waited for.
+    fn is_cancelled(&mut self, packet: &Packet) -> bool {
+        let generation = self.generation;
+        self.cancelled.retain(|cancelled| cancelled.generation == generation);
+        if packet.packet_type == PacketType::Event {
+            return self
+                .cancelled
+                .iter()
+                .any(|cancelled| cancelled.event.is_some() && cancelled.event == packet.name);
+        }
+
+        match self.cancelled.pop_front() {
+            Some(cancelled) => {
+                log::debug!("discarding the answer to {} after it timed out", cancelled.command);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn dispatch(&mut self, packet: Packet) {
+        let Some(name) = packet.name.as_deref() else {
+            return;
+        };
+
+        if let Some((event, events)) = &self.streaming {
+            if event == name {
+                let _ = events.send(packet);
+                return;
+            }
+        }
+        for subscriber in self.subscribers.iter().filter(|subscriber| subscriber.event == name) {
+            let _ = subscriber.events.send(Delivery::Event(packet.clone()));
+        }
+    }
+
+    /// Starts reading packets from the stream on a new thread, ignoring the packets read from the previous stream.
+    fn attach(&mut self, stream: Stream) -> io::Result<()> {
+        let reader = stream.try_clone()?;
+        let tx = self.tx.clone();
+        let generation = self.generation + 1;
+        thread::Builder::new()
+            .name("vici-receiver".to_string())
+            .spawn(move || receive(reader, tx, generation))?;
+
+        self.generation = generation;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn disconnect(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.registered.clear();
+        self.cancelled.clear();
+    }
+
+    /// Connects again after the connection is lost and registers the events of the subscriptions again, returning false if the
+    /// connection is to be closed instead.
+    fn reconnect(&mut self) -> bool {
+        let Some((endpoint, backoff)) = self.reconnect.clone() else {
+            return false;
+        };
+
+        // Commands issued while the connection is lost fail immediately rather than waiting for the connection.
+        while let Some(message) = self.queue.pop_front() {
+            if !self.reject(message) {
+                return false;
+            }
+        }
+
+        for delay in backoff.delays() {
+            if !self.wait(delay) {
+                return false;
+            }
+            if self.connect(&endpoint) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Connects to the endpoint and registers the events of the subscriptions again, returning false if it fails.
+    fn connect(&mut self, endpoint: &Endpoint) -> bool {
+        let Ok(stream) = Stream::connect(endpoint) else {
+            return false;
+        };
+        if self.attach(stream).is_err() || !self.resubscribe() {
+            self.disconnect();
+            return false;
+        }
+
+        for subscriber in &self.subscribers {
+            let _ = subscriber.events.send(Delivery::Reconnected);
+        }
+        true
+    }
+
+    /// Registers the events of the subscriptions, returning false if the connection fails.
+    fn resubscribe(&mut self) -> bool {
+        let events: HashSet<_> = self.subscribers.iter().map(|subscriber| subscriber.event.clone()).collect();
+        for event in events {
+            if self.register(&event, None).is_err() {
+                if self.stream.is_none() {
+                    return false;
+                }
+                // The subscriptions to an event that `charon` no longer knows are ended.
+                self.subscribers.retain(|subscriber| subscriber.event != event);
+            }
+        }
+        true
+    }
+
+    /// Waits for the given delay while rejecting the messages, returning false if the client is closed in the meantime.
+    fn wait(&mut self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        loop {
+            match self.inbox.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(message) => {
+                    if !self.reject(message) {
+                        return false;
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => return true,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+
+    /// Answers the message as if the connection were closed, returning false if the message closes the client.
+    fn reject(&mut self, message: Message) -> bool {
+        match message {
+            Message::Request { reply, .. } | Message::Stream { reply, .. } => {
+                let _ = reply.send(Err(closed()));
+            },
+            Message::Subscribe { reply, .. } => {
+                let _ = reply.send(Err(closed()));
+            },
+            Message::Unsubscribe { id } => self.subscribers.retain(|subscriber| subscriber.id != id),
+            Message::Received(..) => {},
+            Message::Close => return false,
+        }
+        true
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.disconnect();
+    }
+}
+
+impl Backoff {
+    /// Creates a backoff starting with the given delay, which doubles after every failed attempt up to the given maximum.
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        let max_attempts = None;
+        Self {
+            initial,
+            max,
+            max_attempts,
+        }
+    }
+
+    /// Gives up reconnecting after the given number of failed attempts, closing the client.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    fn delays(&self) -> impl Iterator<Item = Duration> + '_ {
+        let mut delay = self.initial;
+        iter::from_fn(move || {
+            let current = delay;
+            delay = delay.saturating_mul(2).min(self.max);
+            Some(current)
+        })
+        .take(self.max_attempts.unwrap_or(usize::MAX))
+    }
+}
+
+impl Default for Backoff {
+    /// Creates a backoff starting with 100 milliseconds up to 30 seconds, retrying indefinitely.
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(30))
+    }
+}
+
+/// Forwards the packets read from the connection to the connection thread until the connection fails.
+fn receive(mut stream: Stream, tx: Sender<Message>, generation: u64) {
+    loop {
+        let received = read_packet(&mut stream);
+        let failed = received.is_err();
+        if tx.send(Message::Received(generation, received)).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Receives from the channel until the deadline, returning `None` if the channel is disconnected.
+fn recv<T>(rx: &Receiver<T>, deadline: Option<Instant>, command: &str) -> Result<Option<T>> {
+    let Some(deadline) = deadline else {
+        return Ok(rx.recv().ok());
+    };
+    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(value) => Ok(Some(value)),
+        Err(RecvTimeoutError::Timeout) => Err(Error::timeout(command)),
+        Err(RecvTimeoutError::Disconnected) => Ok(None),
+    }
+}
+
+fn is_expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| deadline <= Instant::now())
+}
+
+fn closed() -> Error {
+    Error::io(io::Error::new(io::ErrorKind::NotConnected, "connection closed"), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        client::tests::{charon, Sa, Version},
+        testing::MockServer,
+    };
+
+    fn connect() -> (MockServer, SharedClient) {
+        let server = charon(MockServer::bind_tcp().unwrap());
+        let client = SharedClient::connect(server.endpoint()).unwrap();
+        (server, client)
+    }
+
+    fn registered(server: &MockServer, client: &SharedClient) -> Vec<String> {
+        // Commands are sent in order, so the events released before are unregistered once a later command completes.
+        client.request::<Version, _>("version", &()).unwrap();
+        server.registered()
+    }
+
+    #[test]
+    fn request_shared() {
+        let (_server, client) = connect();
+
+        let actual: Version = client.clone().request("version", &()).unwrap();
+        assert_eq!(actual.daemon, "charon");
+
+        let err = client.request::<Version, _>("stats", &()).unwrap_err();
+        assert_eq!(err.to_string(), "unknown command: stats");
+
+        let err = client.request::<(), _>("terminate", &BTreeMap::from([("ike", "gw")])).unwrap_err();
+        assert!(err.is_command());
+    }
+
+    #[test]
+    fn stream_concurrent() {
+        let (server, client) = connect();
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let client = client.clone();
+                thread::spawn(move || {
+                    let sas = client.stream::<BTreeMap<String, Sa>, _>("list-sas", "list-sa", &()).unwrap();
+                    let actual: Vec<_> = sas.map(|sa| sa.unwrap().into_keys().collect::<Vec<_>>()).collect();
+                    let version: Version = client.request("version", &()).unwrap();
+                    (actual, version.version)
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let (actual, version) = worker.join().unwrap();
+            assert_eq!(actual, vec![vec!["gw-01".to_string()], vec!["gw-02".to_string()]]);
+            assert_eq!(version, "5.9.5");
+        }
+        assert_eq!(registered(&server, &client), Vec::<String>::new());
+    }
+
+    #[test]
+    fn subscribe_events() {
+        let (server, client) = connect();
+
+        let mut logs = client.subscribe::<BTreeMap<String, String>>("log").unwrap();
+        assert_eq!(registered(&server, &client), vec!["log".to_string()]);
+
+        let sas = client.stream::<BTreeMap<String, Sa>, _>("list-sas", "list-sa", &()).unwrap();
+        assert_eq!(sas.count(), 2);
+        assert_eq!(logs.next().unwrap().unwrap(), Notification::Event(BTreeMap::new()));
+        assert_eq!(logs.next().unwrap().unwrap(), Notification::Event(BTreeMap::new()));
+
+        drop(logs);
+        assert_eq!(registered(&server, &client), Vec::<String>::new());
+    }
+
+    #[test]
+    fn stream_error() {
+        let (server, client) = connect();
+
+        let mut sas = client.stream::<Sa, _>("list-conns", "list-conn", &()).unwrap();
+        assert_eq!(sas.next().unwrap().unwrap_err().to_string(), "unknown event: list-conn");
+        assert!(sas.next().is_none());
+
+        let sas = client.stream::<Sa, _>("list-conns", "list-sa", &()).unwrap();
+        assert_eq!(sas.finish::<()>().unwrap_err().to_string(), "unknown command: list-conns");
+        assert_eq!(registered(&server, &client), Vec::<String>::new());
+
+        let err = client.subscribe::<Sa>("list-conn").err().unwrap();
+        assert_eq!(err.to_string(), "unknown event: list-conn");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reconnect() {
+        let path = std::env::temp_dir().join(format!("serde-vici-reconnect-{}.sock", std::process::id()));
+        let server = charon(MockServer::bind_unix(&path).unwrap());
+
+        let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(40));
+        let client = SharedClient::connect_with_backoff(server.endpoint(), backoff).unwrap();
+        let mut logs = client.subscribe::<BTreeMap<String, String>>("log").unwrap();
+
+        // Restart the server after the client has failed to connect a few times.
+        drop(server);
+        thread::sleep(Duration::from_millis(100));
+        let server = charon(MockServer::bind_unix(&path).unwrap());
+
+        assert_eq!(logs.next().unwrap().unwrap(), Notification::Reconnected);
+        assert_eq!(registered(&server, &client), vec!["log".to_string()]);
+
+        let sas = client.stream::<BTreeMap<String, Sa>, _>("list-sas", "list-sa", &()).unwrap();
+        assert_eq!(sas.count(), 2);
+        assert_eq!(logs.next().unwrap().unwrap(), Notification::Event(BTreeMap::new()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reconnect_give_up() {
+        let path = std::env::temp_dir().join(format!("serde-vici-give-up-{}.sock", std::process::id()));
+        let server = charon(MockServer::bind_unix(&path).unwrap());
+
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(1)).with_max_attempts(3);
+        let client = SharedClient::connect_with_backoff(server.endpoint(), backoff).unwrap();
+        let mut logs = client.subscribe::<BTreeMap<String, String>>("log").unwrap();
+
+        drop(server);
+
+        assert!(logs.next().is_none());
+        assert!(client.request::<Version, _>("version", &()).unwrap_err().is_io());
+    }
+
+    #[test]
+    fn request_timeout() {
+        let server = charon(MockServer::bind_tcp().unwrap());
+        let backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(1));
+        let client = SharedClient::connect_with_backoff(server.endpoint(), backoff)
+            .unwrap()
+            .with_timeout(Duration::from_millis(20));
+
+        let err = client.request::<(), _>("sleep", &()).unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(err.to_string(), "command timed out: sleep");
+
+        let actual: Version = client.request_with_timeout("version", &(), Duration::from_secs(5)).unwrap();
+        assert_eq!(actual.daemon, "charon");
+
+        let mut logs = client
+            .stream_with_timeout::<BTreeMap<String, String>, _>("sleep", "log", &(), Duration::from_millis(20))
+            .unwrap();
+        assert!(logs.next().unwrap().is_ok());
+        assert!(logs.next().unwrap().unwrap_err().is_timeout());
+        assert!(logs.next().is_none());
+
+        let actual: Version = client.request_with_timeout("version", &(), Duration::from_secs(5)).unwrap();
+        assert_eq!(actual.daemon, "charon");
+        assert_eq!(client.clone().timeout(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn request_timeout_queued() {
+        let (_server, client) = connect();
+
+        // Commands queued behind a slow command time out without being sent.
+        let sleeping = {
+            let client = client.clone();
+            thread::spawn(move || client.request_with_timeout::<(), _>("sleep", &(), Duration::from_secs(5)))
+        };
+        thread::sleep(Duration::from_millis(20));
+
+        let err = client
+            .request_with_timeout::<Version, _>("version", &(), Duration::from_millis(1))
+            .unwrap_err();
+        assert!(err.is_timeout());
+        let sas = client
+            .stream_with_timeout::<Sa, _>("list-sas", "list-sa", &(), Duration::from_millis(1))
+            .unwrap();
+        assert!(sas.finish::<()>().unwrap_err().is_timeout());
+
+        sleeping.join().unwrap().unwrap();
+        let actual: Version = client.request("version", &()).unwrap();
+        assert_eq!(actual.daemon, "charon");
+    }
+
+    #[test]
+    fn request_timeout_keeps_connection() {
+        let (server, client) = connect();
+        let client = client.with_timeout(Duration::from_millis(20));
+
+        // The response of the command that timed out is discarded rather than taken for that of the next command.
+        assert!(client.request::<(), _>("sleep", &()).unwrap_err().is_timeout());
+        let actual: Version = client.request_with_timeout("version", &(), Duration::from_secs(5)).unwrap();
+        assert_eq!(actual.daemon, "charon");
+
+        let logs = client.stream::<BTreeMap<String, String>, _>("sleep", "log", &()).unwrap();
+        assert!(logs.finish::<()>().unwrap_err().is_timeout());
+        let actual: Version = client.request_with_timeout("version", &(), Duration::from_secs(5)).unwrap();
+        assert_eq!(actual.daemon, "charon");
+        assert_eq!(server.connections(), 1);
+    }
+}
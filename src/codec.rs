@@ -0,0 +1,157 @@
+//! Frame VICI packets on asynchronous transports with `tokio_util::codec`.
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    error::{Error, Result},
+    packet::{check_packet_size, decode, encode_into, encoded_len, Packet, MAX_PACKET_SIZE},
+};
+
+/// The maximum size of a frame `charon` accepts by default, excluding its length prefix.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = MAX_PACKET_SIZE;
+
+/// A codec that decodes and encodes whole VICI packets.
+///
+/// The message of a decoded [`Packet`] can be deserialized into any type with [`Packet::deserialize`].
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use bytes::BytesMut;
+/// use serde_vici::{codec::PacketCodec, packet::Packet};
+/// use std::collections::BTreeMap;
+/// use tokio_util::codec::{Decoder, Encoder};
+///
+/// fn main() -> Result<()> {
+///     let mut codec = PacketCodec::new();
+///     let mut buf = BytesMut::new();
+///     codec.encode(Packet::event("log", &BTreeMap::from([("msg", "hello")]))?, &mut buf)?;
+///
+///     let packet = codec.decode(&mut buf)?.unwrap();
+///     let message: BTreeMap<String, String> = packet.deserialize()?;
+///     assert_eq!(message["msg"], "hello");
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PacketCodec {
+    max_frame_size: usize,
+}
+
+impl PacketCodec {
+    /// Creates a codec accepting frames up to [`DEFAULT_MAX_FRAME_SIZE`].
+    pub fn new() -> Self {
+        Self::with_max_frame_size(DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    /// Creates a codec accepting frames up to the given size, excluding their length prefix.
+    pub fn with_max_frame_size(max_frame_size: usize) -> Self {
+        Self { max_frame_size }
+    }
+
+    /// Returns the maximum size of a frame, excluding its length prefix.
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    fn check_frame_size(&self, len: usize) -> Result<()> {
+        check_packet_size(len, self.max_frame_size)
+    }
+}
+
+impl Default for PacketCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        let Some(len) = src.get(..4).map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize) else {
+            return Ok(None);
+        };
+        self.check_frame_size(len)?;
+
+        if src.len() < 4 + len {
+            src.reserve(4 + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(4);
+        let frame = src.split_to(len);
+        decode(&frame).map(Some)
+    }
+}
+
+impl Encoder<&Packet> for PacketCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: &Packet, dst: &mut BytesMut) -> Result<()> {
+        let len = encoded_len(item)?;
+        self.check_frame_size(len - 4)?;
+
+        dst.reserve(len);
+        encode_into(item, dst)
+    }
+}
+
+impl Encoder<Packet> for PacketCodec {
+    type Error = Error;
+
+    fn encode(&mut self, item: Packet, dst: &mut BytesMut) -> Result<()> {
+        self.encode(&item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::packet::PacketType;
+
+    #[test]
+    fn decode_partial() {
+        let mut buf = BytesMut::new();
+        let mut codec = PacketCodec::new();
+        codec.encode(Packet::request("version", &()).unwrap(), &mut buf).unwrap();
+        codec.encode(Packet::new(PacketType::CmdResponse, None, vec![]), &mut buf).unwrap();
+
+        let mut input = BytesMut::new();
+        let mut actual = vec![];
+        for b in buf {
+            input.extend_from_slice(&[b]);
+            if let Some(packet) = codec.decode(&mut input).unwrap() {
+                actual.push(packet);
+            }
+        }
+
+        assert_eq!(
+            actual,
+            vec![
+                Packet::request("version", &()).unwrap(),
+                Packet::new(PacketType::CmdResponse, None, vec![])
+            ]
+        );
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn max_frame_size() {
+        let mut codec = PacketCodec::with_max_frame_size(8);
+        let mut buf = BytesMut::new();
+
+        codec.encode(Packet::request("stats", &()).unwrap(), &mut buf).unwrap();
+        let err = codec.encode(Packet::request("list-conns", &()).unwrap(), &mut buf).unwrap_err();
+        assert_eq!(err.to_string(), "frame of 12 bytes exceeds the maximum of 8 bytes");
+
+        let mut input = BytesMut::from(&[0, 0, 0, 9][..]);
+        let err = codec.decode(&mut input).unwrap_err();
+        assert_eq!(err.to_string(), "frame of 9 bytes exceeds the maximum of 8 bytes");
+    }
+}
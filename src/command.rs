@@ -0,0 +1,687 @@
+//! Decode the outcome of commands reported as `success` and `errmsg` in their responses.
+//!
+//! Most commands of `charon` respond with a message in the following form, where `errmsg` is present only when the command fails:
+//!
+//! ```text
+//! success = yes|no
+//! errmsg = <error message>
+//! ```
+
+use std::{error, fmt, marker::PhantomData};
+
+use serde::{de, forward_to_deserialize_any, Deserialize};
+
+use crate::error::{Error, Result};
+
+/// The name of the newtype struct through which `CommandResult` asks for the options of the deserializer before the response.
+pub(crate) const COMMAND_RESULT_TOKEN: &str = "$serde_vici::private::CommandResult";
+
+/// The outcome of a command, deserialized from its response.
+///
+/// A response with `success = no` is decoded as [`CommandResult::Failure`], while any other response is deserialized as `T`, which
+/// may or may not declare the `success` field itself. The response is decoded in a single pass, observing `success`, `errmsg`, and
+/// `matches` as they are passed on to `T`. These values are decoded with the spellings of `bool` and the parsing of numbers set by the
+/// [`DeserializerOptions`](crate::DeserializerOptions) of the deserializer.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use serde::Deserialize;
+/// use serde_vici::command::CommandResult;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Terminate {
+///     matches: u32,
+/// }
+///
+/// fn main() -> Result<()> {
+///     #[rustfmt::skip]
+///     let data = vec![
+///         // key-value
+///         3, 7, b's', b'u', b'c', b'c', b'e', b's', b's', 0, 2, b'n', b'o',
+///         // key-value
+///         3, 6, b'e', b'r', b'r', b'm', b's', b'g', 0, 11, b'n', b'o', b' ', b'm', b'a', b't', b'c', b'h', b'i', b'n', b'g',
+///         // key-value
+///         3, 7, b'm', b'a', b't', b'c', b'h', b'e', b's', 0, 1, b'0',
+///     ];
+///
+///     let result: CommandResult<Terminate> = serde_vici::from_slice(&data)?;
+///     let err = result.into_result().unwrap_err();
+///     assert_eq!(err.to_string(), "command failed: no matching");
+///     assert_eq!(err.command_error().and_then(|e| e.matches()), Some(0));
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CommandResult<T> {
+    /// The command succeeded with the given response.
+    Success(T),
+
+    /// The command failed.
+    Failure(CommandError),
+}
+
+/// A structure representing a failed command.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommandError {
+    errmsg: Option<String>,
+    matches: Option<u32>,
+}
+
+impl<T> CommandResult<T> {
+    /// Converts this outcome into a `Result`, turning a failure into a `serde_vici::Error` of `Category::Command`.
+    pub fn into_result(self) -> Result<T> {
+        match self {
+            CommandResult::Success(v) => Ok(v),
+            CommandResult::Failure(e) => Err(Error::command(e)),
+        }
+    }
+}
+
+impl CommandError {
+    /// Returns the error message reported by `charon`, if any.
+    pub fn errmsg(&self) -> Option<&str> {
+        self.errmsg.as_deref()
+    }
+
+    /// Returns the number of matching objects reported by commands such as `terminate` or `initiate`, if any.
+    pub fn matches(&self) -> Option<u32> {
+        self.matches
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.errmsg {
+            Some(errmsg) => write!(f, "command failed: {errmsg}"),
+            None => f.write_str("command failed"),
+        }
+    }
+}
+
+impl error::Error for CommandError {}
+
+impl<'de, T> Deserialize<'de> for CommandResult<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let visitor = CommandResultVisitor {
+            options: Options::default(),
+            marker: PhantomData,
+        };
+        deserializer.deserialize_newtype_struct(COMMAND_RESULT_TOKEN, visitor)
+    }
+}
+
+struct CommandResultVisitor<T> {
+    options: Options,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T> de::Visitor<'de> for CommandResultVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = CommandResult<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a command response")
+    }
+
+    /// Receives the options of the [`Deserializer`](crate::Deserializer) followed by the response.
+    fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let true_values = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let false_values = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let lenient = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let visitor = CommandResultVisitor {
+            options: Options {
+                true_values,
+                false_values,
+                lenient,
+            },
+            marker: PhantomData,
+        };
+        seq.next_element_seed(visitor)?.ok_or_else(|| de::Error::invalid_length(3, &self))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> core::result::Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+
+    fn visit_map<A>(self, map: A) -> core::result::Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut response = Response {
+            map,
+            options: self.options,
+            key: None,
+            done: false,
+            success: None,
+            errmsg: None,
+            matches: None,
+        };
+        let result = T::deserialize(&mut response);
+
+        // `success` may follow the elements `T` has not consumed, including those after an element `T` failed on.
+        let drained = response.drain();
+        if response.success == Some(false) {
+            let errmsg = response.errmsg;
+            let matches = response.matches.and_then(|matches| response.options.number(&matches).parse().ok());
+            return Ok(CommandResult::Failure(CommandError { errmsg, matches }));
+        }
+        let value = result?;
+        drained?;
+        Ok(CommandResult::Success(value))
+    }
+}
+
+impl<'de, T> de::DeserializeSeed<'de> for CommandResultVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = CommandResult<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> core::result::Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+/// The options of the deserializer that apply to the values of a response, `yes` and `no` with strict numbers by default.
+struct Options {
+    true_values: Vec<String>,
+    false_values: Vec<String>,
+    lenient: bool,
+}
+
+/// Passes the elements of a response on to the type it is deserialized into, keeping the values that describe its outcome.
+struct Response<A> {
+    map: A,
+    options: Options,
+    key: Option<String>,
+    done: bool,
+    success: Option<bool>,
+    errmsg: Option<String>,
+    matches: Option<String>,
+}
+
+/// A key or value of a response, borrowed from the input if possible.
+enum Str<'de> {
+    Borrowed(&'de str),
+    Owned(String),
+}
+
+impl<'de, A> Response<A>
+where
+    A: de::MapAccess<'de>,
+{
+    /// Consumes the elements left in the response.
+    fn drain(&mut self) -> core::result::Result<(), A::Error> {
+        if self.key.is_some() {
+            de::MapAccess::next_value::<de::IgnoredAny>(self)?;
+        }
+        while de::MapAccess::next_entry::<de::IgnoredAny, de::IgnoredAny>(self)?.is_some() {}
+        Ok(())
+    }
+}
+
+impl<'de, A> de::MapAccess<'de> for Response<A>
+where
+    A: de::MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> core::result::Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        if self.done {
+            return Ok(None);
+        }
+        let Some(key) = self.map.next_key::<Str>()? else {
+            self.done = true;
+            return Ok(None);
+        };
+
+        self.key = Some(key.as_str().to_string());
+        seed.deserialize(StrDeserializer::new(key, &self.options)).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let key = self.key.take();
+        let slot = match key.as_deref() {
+            Some("success") => None,
+            Some("errmsg") => Some(&mut self.errmsg),
+            Some("matches") => Some(&mut self.matches),
+            _ => return self.map.next_value_seed(seed),
+        };
+
+        let value = self.map.next_value::<Str>()?;
+        match slot {
+            Some(slot) => *slot = Some(value.as_str().to_string()),
+            None => self.success = Some(self.options.parse_bool(value.as_str()) != Some(false)),
+        }
+        seed.deserialize(StrDeserializer::new(value, &self.options))
+    }
+}
+
+impl<'de, A> de::Deserializer<'de> for &mut Response<A>
+where
+    A: de::MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_map(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.drain()?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf seq tuple tuple_struct map struct enum identifier
+    }
+}
+
+impl Options {
+    fn parse_bool(&self, v: &str) -> Option<bool> {
+        if self.true_values.iter().any(|t| t == v) {
+            Some(true)
+        } else if self.false_values.iter().any(|f| f == v) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn number<'a>(&self, v: &'a str) -> &'a str {
+        if self.lenient {
+            v.trim()
+        } else {
+            v
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            true_values: vec!["yes".to_string()],
+            false_values: vec!["no".to_string()],
+            lenient: false,
+        }
+    }
+}
+
+impl Str<'_> {
+    fn as_str(&self) -> &str {
+        match self {
+            Str::Borrowed(v) => v,
+            Str::Owned(v) => v,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Str<'de> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct StrVisitor;
+
+        impl<'de> de::Visitor<'de> for StrVisitor {
+            type Value = Str<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Str::Borrowed(v))
+            }
+
+            fn visit_str<E>(self, v: &str) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Str::Owned(v.to_string()))
+            }
+
+            fn visit_string<E>(self, v: String) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Str::Owned(v))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+macro_rules! deserialize_number {
+    ($method:ident => $visit:ident) => {
+        fn $method<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+        where
+            V: de::Visitor<'de>,
+        {
+            match self.options.number(self.value.as_str()).parse() {
+                Ok(v) => visitor.$visit(v),
+                Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(self.value.as_str()), &visitor)),
+            }
+        }
+    };
+}
+
+/// Deserializes a key or value in the same way as the [`Deserializer`](crate::Deserializer) does with the given options.
+struct StrDeserializer<'a, 'de, E> {
+    value: Str<'de>,
+    options: &'a Options,
+    marker: PhantomData<E>,
+}
+
+impl<'a, 'de, E> StrDeserializer<'a, 'de, E> {
+    fn new(value: Str<'de>, options: &'a Options) -> Self {
+        Self {
+            value,
+            options,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, E> de::Deserializer<'de> for StrDeserializer<'_, 'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value {
+            Str::Borrowed(v) => visitor.visit_borrowed_str(v),
+            Str::Owned(v) => visitor.visit_string(v),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.options.parse_bool(self.value.as_str()) {
+            Some(v) => visitor.visit_bool(v),
+            None => Err(de::Error::invalid_value(de::Unexpected::Str(self.value.as_str()), &visitor)),
+        }
+    }
+
+    deserialize_number!(deserialize_i8 => visit_i8);
+    deserialize_number!(deserialize_i16 => visit_i16);
+    deserialize_number!(deserialize_i32 => visit_i32);
+    deserialize_number!(deserialize_i64 => visit_i64);
+    deserialize_number!(deserialize_u8 => visit_u8);
+    deserialize_number!(deserialize_u16 => visit_u16);
+    deserialize_number!(deserialize_u32 => visit_u32);
+    deserialize_number!(deserialize_u64 => visit_u64);
+    deserialize_number!(deserialize_f32 => visit_f32);
+    deserialize_number!(deserialize_f64 => visit_f64);
+
+    fn deserialize_option<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> core::result::Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct enum identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        de::{from_slice, from_slice_with, DeserializerOptions, NumberParsing},
+        error::Category,
+        ser::to_vec,
+    };
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    struct Response<'a> {
+        success: bool,
+        errmsg: Option<&'a str>,
+        matches: Option<u32>,
+    }
+
+    #[derive(Debug, Deserialize, Eq, PartialEq)]
+    struct Load {
+        success: bool,
+    }
+
+    #[test]
+    fn success() {
+        let data = to_vec(&Response {
+            success: true,
+            errmsg: None,
+            matches: Some(1),
+        })
+        .unwrap();
+
+        let actual: CommandResult<Load> = from_slice(&data).unwrap();
+        assert_eq!(actual, CommandResult::Success(Load { success: true }));
+
+        let actual: CommandResult<()> = from_slice(&[]).unwrap();
+        assert_eq!(actual, CommandResult::Success(()));
+    }
+
+    #[test]
+    fn failure() {
+        let data = to_vec(&Response {
+            success: false,
+            errmsg: Some("no matching SAs to terminate found"),
+            matches: Some(0),
+        })
+        .unwrap();
+
+        let actual: CommandResult<Load> = from_slice(&data).unwrap();
+        let CommandResult::Failure(e) = &actual else {
+            panic!("expected a failure: {actual:?}");
+        };
+        assert_eq!(e.errmsg(), Some("no matching SAs to terminate found"));
+        assert_eq!(e.matches(), Some(0));
+
+        let err = actual.into_result().unwrap_err();
+        assert_eq!(err.classify(), Category::Command);
+        assert_eq!(err.to_string(), "command failed: no matching SAs to terminate found");
+    }
+
+    #[test]
+    fn failure_without_errmsg() {
+        let data = to_vec(&Response {
+            success: false,
+            errmsg: None,
+            matches: None,
+        })
+        .unwrap();
+
+        let err = from_slice::<CommandResult<Load>>(&data).unwrap().into_result().unwrap_err();
+        assert_eq!(err.to_string(), "command failed");
+        assert_eq!(err.command_error().and_then(CommandError::errmsg), None);
+    }
+
+    #[test]
+    fn failure_after_invalid_element() {
+        #[derive(Debug, Deserialize)]
+        struct Initiate {
+            #[allow(dead_code)]
+            matches: bool,
+        }
+
+        // `success` follows an element that cannot be deserialized into `T`.
+        #[rustfmt::skip]
+        let data = [
+            // matches = 0
+            3, 7, b'm', b'a', b't', b'c', b'h', b'e', b's', 0, 1, b'0',
+            // success = no
+            3, 7, b's', b'u', b'c', b'c', b'e', b's', b's', 0, 2, b'n', b'o',
+            // errmsg = timeout
+            3, 6, b'e', b'r', b'r', b'm', b's', b'g', 0, 7, b't', b'i', b'm', b'e', b'o', b'u', b't',
+        ];
+
+        let err = from_slice::<CommandResult<Initiate>>(&data).unwrap().into_result().unwrap_err();
+        assert_eq!(err.to_string(), "command failed: timeout");
+        assert_eq!(err.command_error().and_then(CommandError::matches), Some(0));
+
+        let err = from_slice::<CommandResult<Initiate>>(&data[..12]).unwrap_err();
+        assert!(err.is_data());
+    }
+
+    #[test]
+    fn success_borrowed() {
+        let data = to_vec(&Response {
+            success: true,
+            errmsg: Some("none"),
+            matches: Some(2),
+        })
+        .unwrap();
+
+        let actual: CommandResult<std::collections::BTreeMap<&str, &str>> = from_slice(&data).unwrap();
+        assert_eq!(
+            actual,
+            CommandResult::Success([("success", "yes"), ("errmsg", "none"), ("matches", "2")].into_iter().collect())
+        );
+
+        let actual: CommandResult<Response> = from_slice(&data).unwrap();
+        assert_eq!(
+            actual,
+            CommandResult::Success(Response {
+                success: true,
+                errmsg: Some("none"),
+                matches: Some(2),
+            })
+        );
+    }
+
+    #[test]
+    fn options() {
+        #[derive(Serialize)]
+        struct Raw<'a> {
+            success: &'a str,
+            errmsg: &'a str,
+            matches: &'a str,
+        }
+
+        let options = DeserializerOptions::new()
+            .with_bools(["true"], ["false"])
+            .with_numbers(NumberParsing::Lenient);
+        let data = to_vec(&Raw {
+            success: "false",
+            errmsg: "unable to load",
+            matches: " 3",
+        })
+        .unwrap();
+
+        let actual: CommandResult<()> = from_slice_with(&data, &options).unwrap();
+        assert_eq!(
+            actual,
+            CommandResult::Failure(CommandError {
+                errmsg: Some("unable to load".to_string()),
+                matches: Some(3),
+            })
+        );
+
+        let data = to_vec(&Raw {
+            success: "true",
+            errmsg: "none",
+            matches: " 3",
+        })
+        .unwrap();
+
+        let actual: CommandResult<Response> = from_slice_with(&data, &options).unwrap();
+        assert_eq!(
+            actual,
+            CommandResult::Success(Response {
+                success: true,
+                errmsg: Some("none"),
+                matches: Some(3),
+            })
+        );
+        assert!(from_slice::<CommandResult<Response>>(&data).is_err());
+    }
+}
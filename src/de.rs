@@ -1,13 +1,26 @@
 //! Deserialize VICI data to a Rust data structure.
 
-use std::{io, str};
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    fmt, io,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    str,
+};
 
-use serde::de::{self, IntoDeserializer};
+use bytes::Bytes;
+use serde::{
+    de::{self, IntoDeserializer},
+    ser,
+};
 
 use crate::{
+    command::COMMAND_RESULT_TOKEN,
     error::{Error, ErrorCode, Result},
-    read::{IoRead, Read, Reference, SliceRead},
-    value::ListElement,
+    field::ListElement,
+    raw::{RawAccess, RawKind, RAW_SECTION_TOKEN, RAW_VALUE_TOKEN},
+    read::{scan_within, BytesRead, IoRead, Read, Reference, SliceRead, Until},
     ElementType,
 };
 
@@ -55,6 +68,64 @@ pub struct Deserializer<R> {
     level: Option<usize>,
     state: State,
     scratch: Vec<u8>,
+    options: DeserializerOptions,
+    indices: Vec<usize>,
+}
+
+/// Options changing how the [`Deserializer`] decodes values and what input it accepts.
+///
+/// The defaults accept what `charon` sends and what the [`Serializer`](crate::Serializer) writes by default.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use serde::Deserialize;
+/// use serde_vici::de::{DeserializerOptions, NumberParsing};
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Settings {
+///     enabled: bool,
+///     retries: u32,
+/// }
+///
+/// fn main() -> Result<()> {
+///     let options = DeserializerOptions::new()
+///         .with_bools(["yes", "true", "1"], ["no", "false", "0"])
+///         .with_numbers(NumberParsing::Lenient)
+///         .with_max_depth(8);
+///
+///     #[rustfmt::skip]
+///     let input = vec![
+///         // enabled = true
+///         3, 7, b'e', b'n', b'a', b'b', b'l', b'e', b'd', 0, 4, b't', b'r', b'u', b'e',
+///         // retries = " 3"
+///         3, 7, b'r', b'e', b't', b'r', b'i', b'e', b's', 0, 2, b' ', b'3',
+///     ];
+///
+///     let settings: Settings = serde_vici::from_slice_with(&input, &options)?;
+///     assert_eq!(settings, Settings { enabled: true, retries: 3 });
+///     assert!(serde_vici::from_slice::<Settings>(&input).is_err());
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeserializerOptions {
+    bools: (Vec<Cow<'static, str>>, Vec<Cow<'static, str>>),
+    numbers: NumberParsing,
+    validate_indices: bool,
+    max_depth: Option<usize>,
+    max_list_len: Option<usize>,
+}
+
+/// How numbers are parsed from values.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NumberParsing {
+    /// The value must be the number and nothing else.
+    Strict,
+
+    /// Whitespace around the number is ignored.
+    Lenient,
 }
 
 /// Deserialize an instance of type `T` from an IO stream of the VICI protocol.
@@ -75,6 +146,22 @@ where
     Ok(value)
 }
 
+/// Deserialize an instance of type `T` from an IO stream of the VICI protocol using the given options.
+///
+/// # Errors
+/// Deserialization can fail if reading from the stream fails, if the structure of the input does not match the structure expected by
+/// `T`, if the input exceeds the limits of the options, or if `T`'s implementation of `Deserialize` decides that something is wrong
+/// with the data.
+pub fn from_reader_with<R, T>(reader: R, options: &DeserializerOptions) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(IoRead::new(reader)).with_options(options.clone());
+    let value = de::Deserialize::deserialize(&mut deserializer)?;
+    Ok(value)
+}
+
 /// Deserialize an instance of type `T` from bytes of the VICI protocol.
 ///
 /// # Errors
@@ -90,6 +177,170 @@ where
     Ok(value)
 }
 
+/// Deserialize an instance of type `T` from bytes of the VICI protocol using the given options.
+///
+/// # Errors
+/// Deserialization can fail if the structure of the input does not match the structure expected by `T`, if the input exceeds the
+/// limits of the options, or if `T`'s implementation of `Deserialize` decides that something is wrong with the data.
+pub fn from_slice_with<'a, T>(slice: &'a [u8], options: &DeserializerOptions) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::new(SliceRead::new(slice)).with_options(options.clone());
+    let value = de::Deserialize::deserialize(&mut deserializer)?;
+    Ok(value)
+}
+
+/// Deserialize an instance of type `T` from a `Bytes` buffer of the VICI protocol.
+///
+/// The buffer is kept alive by the deserializer, so binary values deserialized as [`SharedBytes`] are handed out as slices of the
+/// buffer without being copied.
+///
+/// # Errors
+/// Deserialization can fail if the structure of the input does not match the structure expected by `T`, for example if `T` is a struct type
+/// but the input contains something other than a VICI section. It can also fail if the structure is correct but `T`'s implementation of
+/// `Deserialize` decides that something is wrong with the data, for example required struct fields are missing from a VICI section.
+pub fn from_bytes<T>(bytes: Bytes) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(BytesRead::new(&bytes));
+    let value = de::Deserialize::deserialize(&mut deserializer)?;
+    Ok(value)
+}
+
+/// Deserialize an instance of type `T` from a `Bytes` buffer of the VICI protocol using the given options.
+///
+/// Binary values deserialized as [`SharedBytes`] are handed out as slices of the buffer, as with [`from_bytes`].
+///
+/// # Errors
+/// Deserialization can fail if the structure of the input does not match the structure expected by `T`, if the input exceeds the
+/// limits of the options, or if `T`'s implementation of `Deserialize` decides that something is wrong with the data.
+pub fn from_bytes_with<T>(bytes: Bytes, options: &DeserializerOptions) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(BytesRead::new(&bytes)).with_options(options.clone());
+    let value = de::Deserialize::deserialize(&mut deserializer)?;
+    Ok(value)
+}
+
+/// Deserialize an instance of type `T` from a frame of an asynchronous IO stream of the VICI protocol.
+///
+/// The frame is a message prefixed with its length as a 32-bit big-endian integer, as written by
+/// [`to_async_writer`](crate::to_async_writer). Only the frame is read, so the stream may stay open or carry further frames. Any stream
+/// implementing `futures_io::AsyncRead` can be used regardless of the runtime driving it.
+///
+/// # Errors
+/// Deserialization can fail if reading from the stream fails, if the stream ends before the frame does, if the frame exceeds
+/// [`MAX_PACKET_SIZE`](crate::packet::MAX_PACKET_SIZE), if the structure of the input does not match the structure expected by `T`,
+/// or if `T`'s implementation of `Deserialize` decides that something is wrong with the data.
+#[cfg(feature = "futures-io")]
+pub async fn from_async_reader<R, T>(mut reader: R) -> Result<T>
+where
+    R: futures_io::AsyncRead + Unpin,
+    T: de::DeserializeOwned,
+{
+    let mut len = [0; 4];
+    crate::async_io::read_exact(&mut reader, &mut len, 0).await?;
+
+    let len = u32::from_be_bytes(len) as usize;
+    crate::packet::check_packet_size(len, crate::packet::MAX_PACKET_SIZE)?;
+    let mut buf = vec![0; len];
+    crate::async_io::read_exact(&mut reader, &mut buf, 4).await?;
+
+    from_slice(&buf)
+}
+
+/// The name of the newtype struct through which `SharedBytes` asks the deserializer for a slice of its buffer.
+const SHARED_BYTES_TOKEN: &str = "$serde_vici::private::SharedBytes";
+
+/// A binary value that shares the buffer it is deserialized from.
+///
+/// When deserialized with [`from_bytes`], the value is a slice of the input buffer and no copy is made, which suits large values such
+/// as certificates and keys. Other deserializers copy the value into a new buffer.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use bytes::Bytes;
+/// use serde::Deserialize;
+/// use serde_vici::de::SharedBytes;
+///
+/// #[derive(Deserialize)]
+/// struct Cert {
+///     data: SharedBytes,
+/// }
+///
+/// fn main() -> Result<()> {
+///     #[rustfmt::skip]
+///     let input = Bytes::from_static(&[
+///         // data = 0x30 0x82
+///         3, 4, b'd', b'a', b't', b'a', 0, 2, 0x30, 0x82,
+///     ]);
+///
+///     let cert: Cert = serde_vici::de::from_bytes(input.clone())?;
+///     assert_eq!(&cert.data[..], &[0x30, 0x82]);
+///     assert_eq!(cert.data.as_ptr(), input[8..].as_ptr());
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SharedBytes(pub Bytes);
+
+/// Sections with arbitrary names, kept with their names in the order they appear.
+///
+/// `charon` reports the children of a connection, the pools, and other objects as sections named after them. Unlike a map, the
+/// sections keep their order, and unlike a list of sections, their names are kept. They are serialized back as named sections.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use serde::Deserialize;
+/// use serde_vici::de::Named;
+///
+/// #[derive(Deserialize)]
+/// struct Child {
+///     mode: String,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Conn {
+///     children: Named<Child>,
+/// }
+///
+/// fn main() -> Result<()> {
+///     #[rustfmt::skip]
+///     let input = vec![
+///         // children
+///         1, 8, b'c', b'h', b'i', b'l', b'd', b'r', b'e', b'n',
+///         // net-2
+///         1, 5, b'n', b'e', b't', b'-', b'2',
+///         // mode = TUNNEL
+///         3, 4, b'm', b'o', b'd', b'e', 0, 6, b'T', b'U', b'N', b'N', b'E', b'L',
+///         // net-2 end
+///         2,
+///         // net-1
+///         1, 5, b'n', b'e', b't', b'-', b'1',
+///         // mode = TRANSPORT
+///         3, 4, b'm', b'o', b'd', b'e', 0, 9, b'T', b'R', b'A', b'N', b'S', b'P', b'O', b'R', b'T',
+///         // net-1 end
+///         2,
+///         // children end
+///         2,
+///     ];
+///
+///     let conn: Conn = serde_vici::from_slice(&input)?;
+///     let names: Vec<_> = conn.children.iter().map(|(name, child)| (name.as_str(), child.mode.as_str())).collect();
+///     assert_eq!(names, [("net-2", "TUNNEL"), ("net-1", "TRANSPORT")]);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Named<T>(pub Vec<(String, T)>);
+
 impl<'de, R> Deserializer<R>
 where
     R: Read<'de>,
@@ -109,7 +360,56 @@ where
             level,
             state,
             scratch,
+            options: DeserializerOptions::default(),
+            indices: vec![],
+        }
+    }
+
+    /// Sets the options changing how values are decoded.
+    pub fn with_options(mut self, options: DeserializerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Enters a section or a list, failing if it is nested too deeply.
+    fn enter(&mut self) -> Result<()> {
+        let level = self.level.map_or(1, |l| l + 1);
+        if let Some(max_depth) = self.options.max_depth.filter(|&max_depth| level > max_depth) {
+            return Err(Error::data(
+                ErrorCode::Message(format!("nesting exceeds the maximum depth of {max_depth}")),
+                None,
+                Some(self.read.position()),
+            ));
         }
+        self.level = Some(level);
+        Ok(())
+    }
+
+    /// Counts an item of the current list or list of sections, failing if there are too many, and returns its index.
+    fn next_index(&mut self) -> Result<usize> {
+        let index = self.indices.last_mut().map_or(0, |index| {
+            *index += 1;
+            *index - 1
+        });
+        if let Some(max_list_len) = self.options.max_list_len.filter(|&max_list_len| index >= max_list_len) {
+            return Err(Error::data(
+                ErrorCode::Message(format!("list exceeds the maximum length of {max_list_len}")),
+                None,
+                Some(self.read.position()),
+            ));
+        }
+        Ok(index)
+    }
+
+    /// Visits the items of a list, or the sections of a list of sections while counting their indexes.
+    fn visit_seq<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.indices.push(0);
+        let value = visitor.visit_seq(&mut *self);
+        self.indices.pop();
+        value
     }
 
     #[inline]
@@ -119,30 +419,65 @@ where
 
     #[inline]
     fn parse_str(&mut self) -> Result<Reference<'de, '_, str>> {
+        parse_str(&mut self.read, &self.state, &mut self.scratch)
+    }
+
+    #[inline]
+    fn parse_raw_value(&mut self) -> Result<Reference<'de, '_, [u8]>> {
         match &self.state {
-            State::Key | State::SectionKey | State::ListName => {
-                self.scratch.clear();
-                self.read.parse_key(&mut self.scratch)
-            },
             State::Value | State::ListItem(_) => {
                 self.scratch.clear();
-                self.read.parse_value(&mut self.scratch)
+                self.read.parse_value_raw(&mut self.scratch)
             },
-            State::None => Err(Error::io(io::Error::from(io::ErrorKind::InvalidData), Some(self.read.position()))),
+            _ => Err(Error::io(io::Error::from(io::ErrorKind::InvalidData), Some(self.read.position()))),
         }
     }
 
     #[inline]
-    fn parse_raw_value(&mut self) -> Result<Reference<'de, '_, [u8]>> {
+    fn parse_shared_value(&mut self) -> Result<Bytes> {
         match &self.state {
             State::Value | State::ListItem(_) => {
                 self.scratch.clear();
-                self.read.parse_value_raw(&mut self.scratch)
+                self.read.parse_value_shared(&mut self.scratch)
             },
             _ => Err(Error::io(io::Error::from(io::ErrorKind::InvalidData), Some(self.read.position()))),
         }
     }
 
+    /// Hands out the encoded bytes of the current section, or of the current section, list, or value as the variant
+    /// of its kind if `any` is true.
+    fn deserialize_raw<V>(&mut self, any: bool, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (kind, until) = match self.state {
+            State::None if self.level.is_none() => (RawKind::Section, Until::Eof),
+            State::None | State::ListItem(ListElement::Section) => (RawKind::Section, Until::SectionEnd),
+            State::ListItem(ListElement::String) if any => (RawKind::List, Until::ListEnd),
+            State::Value if any => (RawKind::Value, Until::Eof),
+            State::ListItem(ListElement::String) => return Err(de::Error::invalid_type(de::Unexpected::Other("list"), &visitor)),
+            _ => return Err(de::Error::invalid_type(de::Unexpected::Other("value"), &visitor)),
+        };
+
+        self.scratch.clear();
+        let raw = match kind {
+            RawKind::Value => self.read.parse_value_raw(&mut self.scratch)?,
+            RawKind::Section | RawKind::List => self.read.parse_raw(until, &mut self.scratch)?,
+        };
+        if kind != RawKind::Value && until != Until::Eof {
+            self.level = self.level.map(|l| l - 1).filter(|&l| l > 0);
+            self.state = State::None;
+        }
+
+        if any {
+            return visitor.visit_enum(RawAccess::new(kind, raw));
+        }
+        match raw {
+            Reference::Borrowed(v) => visitor.visit_borrowed_bytes(v),
+            Reference::Copied(v) => visitor.visit_bytes(v),
+        }
+    }
+
     #[inline]
     fn peek(&mut self) -> Result<usize> {
         match &self.state {
@@ -170,6 +505,13 @@ impl<'a> Deserializer<SliceRead<'a>> {
     }
 }
 
+impl<'a> Deserializer<BytesRead<'a>> {
+    /// Creates a VICI deserializer from a `Bytes` buffer.
+    pub fn from_bytes(bytes: &'a Bytes) -> Self {
+        Deserializer::new(BytesRead::new(bytes))
+    }
+}
+
 macro_rules! deserialize_number {
     ($method:ident => $visit:ident) => {
         #[inline]
@@ -177,8 +519,13 @@ macro_rules! deserialize_number {
         where
             V: de::Visitor<'de>,
         {
+            let numbers = self.options.numbers;
             let result = self.parse_str()?;
-            let value = result.parse().map_err::<Self::Error, _>(de::Error::custom)?;
+            let input = match numbers {
+                NumberParsing::Strict => &*result,
+                NumberParsing::Lenient => result.trim(),
+            };
+            let value = input.parse().map_err::<Self::Error, _>(de::Error::custom)?;
             visitor.$visit(value)
         }
     };
@@ -208,10 +555,11 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let input = self.parse_str()?;
+        let (true_values, false_values) = &self.options.bools;
+        let input = parse_str(&mut self.read, &self.state, &mut self.scratch)?;
         match &*input {
-            "yes" => visitor.visit_bool(true),
-            "no" => visitor.visit_bool(false),
+            v if true_values.iter().any(|t| t == v) => visitor.visit_bool(true),
+            v if false_values.iter().any(|f| f == v) => visitor.visit_bool(false),
             _ => Err(Error::io(io::Error::from(io::ErrorKind::InvalidData), Some(self.read.position()))),
         }
     }
@@ -226,7 +574,16 @@ where
     deserialize_number!(deserialize_u64 => visit_u64);
     deserialize_number!(deserialize_f32 => visit_f32);
     deserialize_number!(deserialize_f64 => visit_f64);
-    deserialize_number!(deserialize_char => visit_char);
+
+    #[inline]
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let result = self.parse_str()?;
+        let value = result.parse().map_err::<Self::Error, _>(de::Error::custom)?;
+        visitor.visit_char(value)
+    }
 
     #[inline]
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
@@ -301,10 +658,21 @@ where
     }
 
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if name == SHARED_BYTES_TOKEN {
+            let bytes = self.parse_shared_value()?;
+            return visit_shared(visitor, bytes);
+        }
+        if name == RAW_SECTION_TOKEN || name == RAW_VALUE_TOKEN {
+            return self.deserialize_raw(name == RAW_VALUE_TOKEN, visitor);
+        }
+        if name == COMMAND_RESULT_TOKEN {
+            return visitor.visit_seq(CommandAccess { de: self, index: 0 });
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
@@ -313,7 +681,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_seq(self)
+        self.visit_seq(visitor)
     }
 
     #[inline]
@@ -321,7 +689,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_seq(self)
+        self.visit_seq(visitor)
     }
 
     #[inline]
@@ -329,7 +697,7 @@ where
     where
         V: de::Visitor<'de>,
     {
-        visitor.visit_seq(self)
+        self.visit_seq(visitor)
     }
 
     #[inline]
@@ -370,7 +738,27 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        // Ignored elements are skipped by their lengths and end markers, without decoding or validating their contents.
+        let until = match self.state {
+            State::Key | State::SectionKey | State::ListName => {
+                self.read.skip_key()?;
+                return visitor.visit_unit();
+            },
+            State::Value => {
+                self.read.skip_value()?;
+                return visitor.visit_unit();
+            },
+            State::None if self.level.is_none() => Until::Eof,
+            State::None | State::ListItem(ListElement::Section) => Until::SectionEnd,
+            State::ListItem(ListElement::String) => Until::ListEnd,
+        };
+
+        scan_within(&mut self.read, until, None, self.level.unwrap_or(0), self.options.max_depth)?;
+        if until != Until::Eof {
+            self.level = self.level.map(|l| l - 1).filter(|&l| l > 0);
+            self.state = State::None;
+        }
+        visitor.visit_unit()
     }
 
     #[inline]
@@ -392,6 +780,7 @@ where
     {
         match self.parse_element_type()? {
             ElementType::ListItem if matches!(self.state, State::ListItem(ListElement::String)) => {
+                self.next_index()?;
                 self.state = State::Value;
                 let value = seed.deserialize(&mut **self).map(Some)?;
 
@@ -409,10 +798,20 @@ where
                 Ok(None)
             },
             ElementType::SectionStart => {
-                self.level = Some(self.level.map_or(1, |l| l + 1));
+                self.enter()?;
+                let expected = self.next_index()?;
 
                 self.state = State::SectionKey;
-                let _index = self.parse_str()?;
+                let pos = self.read.position();
+                let validate = self.options.validate_indices;
+                let index = self.parse_str()?;
+                if validate && *index != *expected.to_string() {
+                    return Err(Error::data(
+                        ErrorCode::Message(format!("invalid section index {:?}, expected \"{expected}\"", &*index)),
+                        None,
+                        Some(pos),
+                    ));
+                }
 
                 self.state = State::ListItem(ListElement::Section);
                 let value = seed.deserialize(&mut **self).map(Some)?;
@@ -442,12 +841,12 @@ where
     {
         match self.parse_element_type() {
             Ok(ElementType::SectionStart) => {
-                self.level = Some(self.level.map_or(1, |l| l + 1));
+                self.enter()?;
                 self.state = State::SectionKey;
                 seed.deserialize(&mut **self).map(Some)
             },
             Ok(ElementType::ListStart) => {
-                self.level = Some(self.level.map_or(1, |l| l + 1));
+                self.enter()?;
                 self.state = State::ListName;
                 seed.deserialize(&mut **self).map(Some)
             },
@@ -491,8 +890,323 @@ where
     }
 }
 
+/// Hands the options that apply to the values of a command response to `CommandResult`, followed by the response itself.
+struct CommandAccess<'a, R> {
+    de: &'a mut Deserializer<R>,
+    index: usize,
+}
+
+impl<'de, R> de::SeqAccess<'de> for CommandAccess<'_, R>
+where
+    R: Read<'de>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let (true_values, false_values) = &self.de.options.bools;
+        let spellings = |values: &[Cow<'static, str>]| -> Vec<String> { values.iter().map(|v| v.to_string()).collect() };
+
+        self.index += 1;
+        match self.index {
+            1 => seed
+                .deserialize(IntoDeserializer::<Error>::into_deserializer(spellings(true_values)))
+                .map(Some),
+            2 => seed
+                .deserialize(IntoDeserializer::<Error>::into_deserializer(spellings(false_values)))
+                .map(Some),
+            3 => {
+                let lenient = self.de.options.numbers == NumberParsing::Lenient;
+                seed.deserialize(IntoDeserializer::<Error>::into_deserializer(lenient)).map(Some)
+            },
+            4 => seed.deserialize(&mut *self.de).map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl DeserializerOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self {
+            bools: (vec![Cow::Borrowed("yes")], vec![Cow::Borrowed("no")]),
+            numbers: NumberParsing::Strict,
+            validate_indices: false,
+            max_depth: None,
+            max_list_len: None,
+        }
+    }
+
+    /// Sets the spellings accepted for `true` and `false`, `yes` and `no` by default.
+    pub fn with_bools<T, F>(mut self, true_values: T, false_values: F) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<Cow<'static, str>>,
+        F: IntoIterator,
+        F::Item: Into<Cow<'static, str>>,
+    {
+        self.bools = (
+            true_values.into_iter().map(Into::into).collect(),
+            false_values.into_iter().map(Into::into).collect(),
+        );
+        self
+    }
+
+    /// Sets how numbers are parsed, strictly by default.
+    pub fn with_numbers(mut self, numbers: NumberParsing) -> Self {
+        self.numbers = numbers;
+        self
+    }
+
+    /// Sets whether the names of the sections in a list of sections must be their indexes `0`, `1`, ..., as written by the
+    /// [`Serializer`](crate::Serializer).
+    ///
+    /// The names are ignored by default, so that sections named by `charon` can be deserialized as a list in the order they appear.
+    pub fn with_index_validation(mut self, validate: bool) -> Self {
+        self.validate_indices = validate;
+        self
+    }
+
+    /// Limits how deeply sections and lists can be nested, without a limit by default.
+    ///
+    /// The limit applies to the elements that are skipped as well, such as the sections of a struct's unknown fields, but not to those
+    /// copied as they are into a [`RawSection`](crate::raw::RawSection) or [`RawValue`](crate::raw::RawValue).
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Limits the number of items of a list or sections of a list of sections, without a limit by default.
+    pub fn with_max_list_len(mut self, max_list_len: usize) -> Self {
+        self.max_list_len = Some(max_list_len);
+        self
+    }
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for SharedBytes {
+    type Target = Bytes;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Bytes> for SharedBytes {
+    fn from(v: Bytes) -> Self {
+        SharedBytes(v)
+    }
+}
+
+impl From<SharedBytes> for Bytes {
+    fn from(v: SharedBytes) -> Self {
+        v.0
+    }
+}
+
+impl ser::Serialize for SharedBytes {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for SharedBytes {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(SHARED_BYTES_TOKEN, SharedBytesVisitor)
+    }
+}
+
+struct SharedBytesVisitor;
+
+impl<'de> de::Visitor<'de> for SharedBytesVisitor {
+    type Value = SharedBytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a binary value")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> core::result::Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(self)
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        let shared = SHARED.take().filter(|bytes| bytes.as_ptr() == v.as_ptr() && bytes.len() == v.len());
+        Ok(SharedBytes(shared.unwrap_or_else(|| Bytes::copy_from_slice(v))))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(SharedBytes(Bytes::from(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(SharedBytes(Bytes::copy_from_slice(v.as_bytes())))
+    }
+}
+
+/// Parses the current key or value as a string, borrowing only the parts of the deserializer it reads from.
+fn parse_str<'de, 's, R>(read: &'s mut R, state: &State, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>>
+where
+    R: Read<'de>,
+{
+    match state {
+        State::Key | State::SectionKey | State::ListName => {
+            scratch.clear();
+            read.parse_key(scratch)
+        },
+        State::Value | State::ListItem(_) => {
+            scratch.clear();
+            read.parse_value(scratch)
+        },
+        State::None => Err(Error::io(io::Error::from(io::ErrorKind::InvalidData), Some(read.position()))),
+    }
+}
+
+thread_local! {
+    /// The slice of the buffer being handed to the visitor of [`SharedBytes`].
+    static SHARED: Cell<Option<Bytes>> = const { Cell::new(None) };
+}
+
+/// Hands a slice of the buffer to the visitor, as it is if the visitor is that of [`SharedBytes`], or copied for any other visitor.
+///
+/// The data model of Serde has no type that carries a `Bytes`, so the slice is left aside while the visitor is given its contents,
+/// and the visitor of [`SharedBytes`] takes it if the contents it is given are that slice.
+fn visit_shared<'de, V>(visitor: V, bytes: Bytes) -> Result<V::Value>
+where
+    V: de::Visitor<'de>,
+{
+    SHARED.set(Some(bytes.clone()));
+    let value = visitor.visit_bytes(&bytes);
+    SHARED.take();
+    value
+}
+
+impl<T> Deref for Named<T> {
+    type Target = Vec<(String, T)>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Named<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> From<Vec<(String, T)>> for Named<T> {
+    fn from(v: Vec<(String, T)>) -> Self {
+        Named(v)
+    }
+}
+
+impl<T> From<Named<T>> for Vec<(String, T)> {
+    fn from(v: Named<T>) -> Self {
+        v.0
+    }
+}
+
+impl<T> FromIterator<(String, T)> for Named<T> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (String, T)>,
+    {
+        Named(iter.into_iter().collect())
+    }
+}
+
+impl<T> IntoIterator for Named<T> {
+    type Item = (String, T);
+    type IntoIter = std::vec::IntoIter<(String, T)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<T> ser::Serialize for Named<T>
+where
+    T: ser::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        use ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de, T> de::Deserialize<'de> for Named<T>
+where
+    T: de::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct NamedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> de::Visitor<'de> for NamedVisitor<T>
+        where
+            T: de::Deserialize<'de>,
+        {
+            type Value = Named<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("named sections")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> core::result::Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut sections = Vec::with_capacity(map.size_hint().unwrap_or_default());
+                while let Some(entry) = map.next_entry()? {
+                    sections.push(entry);
+                }
+                Ok(Named(sections))
+            }
+        }
+
+        deserializer.deserialize_map(NamedVisitor(PhantomData))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use indexmap::{indexmap, IndexMap};
     use pretty_assertions::assert_eq;
     use serde_derive::Deserialize;
@@ -1069,4 +1783,310 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn deserialize_bytes_shared() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct CertResponse {
+            r#type: String,
+            data: SharedBytes,
+            chain: Vec<SharedBytes>,
+            crl: Option<SharedBytes>,
+        }
+
+        #[rustfmt::skip]
+        let data = Bytes::from_static(&[
+            // type = X509
+            3, 4, b't', b'y', b'p', b'e', 0, 4, b'X', b'5', b'0', b'9',
+            // data = 0x00 0x01 0x02 0x03
+            3, 4, b'd', b'a', b't', b'a', 0, 4, 0x00, 0x01, 0x02, 0x03,
+            // chain
+            4, 5, b'c', b'h', b'a', b'i', b'n',
+            // 0x04 0x05
+            5, 0, 2, 0x04, 0x05,
+            // chain end
+            6,
+            // crl =
+            3, 3, b'c', b'r', b'l', 0, 0,
+        ]);
+
+        let actual: CertResponse = from_bytes(data.clone()).unwrap();
+        assert_eq!(
+            actual,
+            CertResponse {
+                r#type: "X509".to_string(),
+                data: SharedBytes(Bytes::from_static(&[0x00, 0x01, 0x02, 0x03])),
+                chain: vec![SharedBytes(Bytes::from_static(&[0x04, 0x05]))],
+                crl: None,
+            }
+        );
+        assert_eq!(actual.data.as_ptr(), data[20..].as_ptr());
+        assert_eq!(actual.chain[0].as_ptr(), data[34..].as_ptr());
+
+        let actual: CertResponse = from_slice(&data).unwrap();
+        assert_eq!(&actual.data[..], &[0x00, 0x01, 0x02, 0x03]);
+        assert_ne!(actual.data.as_ptr(), data[20..].as_ptr());
+
+        let actual: CertResponse = from_reader(&data[..]).unwrap();
+        assert_eq!(&actual.chain[0][..], &[0x04, 0x05]);
+    }
+
+    #[test]
+    fn deserialize_ignored() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Sa {
+            state: String,
+        }
+
+        #[rustfmt::skip]
+        let data = [
+            // gw-01
+            1, 5, b'g', b'w', b'-', b'0', b'1',
+            // uniqueid = 0xff 0xfe
+            3, 8, b'u', b'n', b'i', b'q', b'u', b'e', b'i', b'd', 0, 2, 0xff, 0xfe,
+            // state = ESTABLISHED
+            3, 5, b's', b't', b'a', b't', b'e', 0, 11, b'E', b'S', b'T', b'A', b'B', b'L', b'I', b'S', b'H', b'E', b'D',
+            // child-sas
+            1, 9, b'c', b'h', b'i', b'l', b'd', b'-', b's', b'a', b's',
+            // 0xff
+            1, 1, 0xff,
+            // local-ts
+            4, 8, b'l', b'o', b'c', b'a', b'l', b'-', b't', b's',
+            // 0xfe
+            5, 0, 1, 0xfe,
+            // local-ts end
+            6,
+            // 0xff end
+            2,
+            // child-sas end
+            2,
+            // gw-01 end
+            2,
+        ];
+
+        let expected = indexmap! {
+            "gw-01".to_string() => Sa {
+                state: "ESTABLISHED".to_string(),
+            },
+        };
+        assert_eq!(from_slice::<IndexMap<String, Sa>>(&data).unwrap(), expected);
+        assert_eq!(from_reader::<_, IndexMap<String, Sa>>(&data[..]).unwrap(), expected);
+        assert_eq!(from_bytes::<IndexMap<String, Sa>>(Bytes::copy_from_slice(&data)).unwrap(), expected);
+
+        let actual: de::IgnoredAny = from_slice(&data).unwrap();
+        assert_eq!(actual, de::IgnoredAny);
+
+        let err = from_slice::<IndexMap<String, Sa>>(&data[..data.len() - 3]).unwrap_err();
+        assert_eq!(err.to_string(), "EOF while parsing element type at position 70");
+
+        let err = from_reader::<_, IndexMap<String, Sa>>(&data[..54]).unwrap_err();
+        assert_eq!(err.to_string(), "EOF while parsing key at position 53");
+    }
+
+    #[test]
+    fn deserialize_section_list_indices() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Lease {
+            address: String,
+        }
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Pool {
+            leases: Vec<Lease>,
+        }
+
+        #[rustfmt::skip]
+        let data = [
+            // leases
+            1, 6, b'l', b'e', b'a', b's', b'e', b's',
+            // 0
+            1, 1, b'0',
+            // address = 10.0.0.1
+            3, 7, b'a', b'd', b'd', b'r', b'e', b's', b's', 0, 8, b'1', b'0', b'.', b'0', b'.', b'0', b'.', b'1',
+            // 0 end
+            2,
+            // 2
+            1, 1, b'2',
+            // address = 10.0.0.2
+            3, 7, b'a', b'd', b'd', b'r', b'e', b's', b's', 0, 8, b'1', b'0', b'.', b'0', b'.', b'0', b'.', b'2',
+            // 2 end
+            2,
+            // leases end
+            2,
+        ];
+
+        let expected = Pool {
+            leases: vec![
+                Lease {
+                    address: "10.0.0.1".to_string(),
+                },
+                Lease {
+                    address: "10.0.0.2".to_string(),
+                },
+            ],
+        };
+        assert_eq!(from_slice::<Pool>(&data).unwrap(), expected);
+
+        let mut de = Deserializer::from_slice(&data).with_options(DeserializerOptions::new().with_index_validation(true));
+        let err = de::Deserialize::deserialize(&mut de).map(|_: Pool| ()).unwrap_err();
+        assert_eq!(err.to_string(), "invalid section index \"2\", expected \"1\" at position 32");
+
+        let mut data = data;
+        data[33] = b'1';
+        let mut de = Deserializer::from_reader(&data[..]).with_options(DeserializerOptions::new().with_index_validation(true));
+        assert_eq!(<Pool as de::Deserialize>::deserialize(&mut de).unwrap(), expected);
+    }
+
+    #[test]
+    fn deserialize_named() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Pool {
+            addrs: String,
+        }
+
+        #[rustfmt::skip]
+        let data = [
+            // rw
+            1, 2, b'r', b'w',
+            // addrs = 10.0.0.0/24
+            3, 5, b'a', b'd', b'd', b'r', b's', 0, 11, b'1', b'0', b'.', b'0', b'.', b'0', b'.', b'0', b'/', b'2', b'4',
+            // rw end
+            2,
+            // guests
+            1, 6, b'g', b'u', b'e', b's', b't', b's',
+            // addrs = 10.1.0.0/24
+            3, 5, b'a', b'd', b'd', b'r', b's', 0, 11, b'1', b'0', b'.', b'1', b'.', b'0', b'.', b'0', b'/', b'2', b'4',
+            // guests end
+            2,
+        ];
+
+        let actual: Named<Pool> = from_slice(&data).unwrap();
+        assert_eq!(
+            actual,
+            Named(vec![
+                (
+                    "rw".to_string(),
+                    Pool {
+                        addrs: "10.0.0.0/24".to_string(),
+                    }
+                ),
+                (
+                    "guests".to_string(),
+                    Pool {
+                        addrs: "10.1.0.0/24".to_string(),
+                    }
+                ),
+            ])
+        );
+
+        let actual: Named<BTreeMap<String, String>> = from_reader(&data[..]).unwrap();
+        assert_eq!(crate::to_vec(&actual).unwrap(), data);
+    }
+
+    #[test]
+    fn deserialize_options() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Settings {
+            mobike: bool,
+            version: u32,
+            local_addrs: Vec<String>,
+            children: BTreeMap<String, BTreeMap<String, String>>,
+        }
+
+        #[rustfmt::skip]
+        let data = [
+            // mobike = true
+            3, 6, b'm', b'o', b'b', b'i', b'k', b'e', 0, 4, b't', b'r', b'u', b'e',
+            // version = "2 "
+            3, 7, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0, 2, b'2', b' ',
+            // local_addrs
+            4, 11, b'l', b'o', b'c', b'a', b'l', b'_', b'a', b'd', b'd', b'r', b's',
+            // 192.0.2.1
+            5, 0, 9, b'1', b'9', b'2', b'.', b'0', b'.', b'2', b'.', b'1',
+            // 192.0.2.2
+            5, 0, 9, b'1', b'9', b'2', b'.', b'0', b'.', b'2', b'.', b'2',
+            // local_addrs end
+            6,
+            // children
+            1, 8, b'c', b'h', b'i', b'l', b'd', b'r', b'e', b'n',
+            // net
+            1, 3, b'n', b'e', b't',
+            // mode = tunnel
+            3, 4, b'm', b'o', b'd', b'e', 0, 6, b't', b'u', b'n', b'n', b'e', b'l',
+            // net end
+            2,
+            // children end
+            2,
+        ];
+
+        let err = from_slice::<Settings>(&data).unwrap_err();
+        assert!(err.is_io());
+
+        let true_values = vec!["yes".to_string(), "true".to_string()];
+        let options = DeserializerOptions::new().with_bools(true_values, ["no", "false"]);
+        let err = from_slice_with::<Settings>(&data, &options).unwrap_err();
+        assert_eq!(err.to_string(), "invalid digit found in string");
+
+        let options = options.with_numbers(NumberParsing::Lenient);
+        let actual: Settings = from_slice_with(&data, &options).unwrap();
+        assert_eq!(actual.version, 2);
+        assert!(actual.mobike);
+        assert_eq!(actual.children["net"]["mode"], "tunnel");
+        assert_eq!(from_reader_with::<_, Settings>(&data[..], &options).unwrap(), actual);
+        assert_eq!(
+            from_bytes_with::<Settings>(Bytes::copy_from_slice(&data), &options).unwrap(),
+            actual
+        );
+
+        let err = from_slice_with::<Settings>(&data, &options.clone().with_max_depth(1)).unwrap_err();
+        assert_eq!(err.to_string(), "nesting exceeds the maximum depth of 1 at position 76");
+        assert!(from_slice_with::<Settings>(&data, &options.clone().with_max_depth(2)).is_ok());
+
+        // The limit applies to the sections that are skipped.
+        let err = from_slice_with::<de::IgnoredAny>(&data, &options.clone().with_max_depth(1)).unwrap_err();
+        assert_eq!(err.to_string(), "nesting exceeds the maximum depth of 1 at position 76");
+        assert!(from_slice_with::<de::IgnoredAny>(&data, &options.clone().with_max_depth(2)).is_ok());
+
+        let err = from_slice_with::<Settings>(&data, &options.clone().with_max_list_len(1)).unwrap_err();
+        assert_eq!(err.to_string(), "list exceeds the maximum length of 1 at position 53");
+        assert!(from_slice_with::<Settings>(&data, &options.with_max_list_len(2)).is_ok());
+    }
+
+    #[cfg(feature = "futures-io")]
+    #[test]
+    fn deserialize_async_reader() {
+        use futures::{
+            executor::block_on,
+            io::{repeat, AsyncReadExt},
+        };
+
+        #[rustfmt::skip]
+        let data = vec![
+            // length
+            0, 0, 0, 28,
+            // key1 = value1
+            3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+            // key2 = value2
+            3, 4, b'k', b'e', b'y', b'2', 0, 6, b'v', b'a', b'l', b'u', b'e', b'2',
+        ];
+
+        let actual: IndexMap<String, String> = block_on(from_async_reader(&data[..])).unwrap();
+        assert_eq!(
+            actual,
+            indexmap! {
+                "key1".to_string() => "value1".to_string(),
+                "key2".to_string() => "value2".to_string(),
+            }
+        );
+
+        // The stream never ends after the frame.
+        let actual: IndexMap<String, String> = block_on(from_async_reader((&data[..]).chain(repeat(0)))).unwrap();
+        assert_eq!(actual.len(), 2);
+
+        let err = block_on(from_async_reader::<_, IndexMap<String, String>>((&data[..]).take(20))).unwrap_err();
+        assert_eq!(err.to_string(), "EOF while parsing packet at position 20");
+
+        let err = block_on(from_async_reader::<_, IndexMap<String, String>>(&[0xff, 0xff, 0xff, 0xff][..])).unwrap_err();
+        assert_eq!(err.to_string(), "frame of 4294967295 bytes exceeds the maximum of 524288 bytes");
+    }
 }
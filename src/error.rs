@@ -9,6 +9,8 @@ use std::{
 
 use serde::{de, ser};
 
+use crate::command::CommandError;
+
 /// A structure representing all possible errors that can occur when serializing or deserializing VICI data.
 pub struct Error {
     err: Box<ErrorImpl>,
@@ -34,11 +36,18 @@ impl Error {
     /// - `Category::Io` - failure to read or write bytes on an IO stream
     /// - `Category::Data` - invalid data
     /// - `Category::Eof` - unexpected end of the input data
+    /// - `Category::Command` - a command reported its failure in the response
+    /// - `Category::Timeout` - a command did not complete in time
     pub fn classify(&self) -> Category {
         match self.err.code {
             ErrorCode::Io(_) => Category::Io,
             ErrorCode::Message(_) | ErrorCode::InvalidUnicodeCodePoint => Category::Data,
-            ErrorCode::EofWhileParsingElementType | ErrorCode::EofWhileParsingKey | ErrorCode::EofWhileParsingValue => Category::Eof,
+            ErrorCode::EofWhileParsingPacket
+            | ErrorCode::EofWhileParsingElementType
+            | ErrorCode::EofWhileParsingKey
+            | ErrorCode::EofWhileParsingValue => Category::Eof,
+            ErrorCode::Command(_) => Category::Command,
+            ErrorCode::Timeout(_) => Category::Timeout,
         }
     }
 
@@ -57,6 +66,24 @@ impl Error {
         self.classify() == Category::Eof
     }
 
+    /// Returns true if this error was caused by a command reporting its failure.
+    pub fn is_command(&self) -> bool {
+        self.classify() == Category::Command
+    }
+
+    /// Returns true if this error was caused by a command not completing in time.
+    pub fn is_timeout(&self) -> bool {
+        self.classify() == Category::Timeout
+    }
+
+    /// Returns the failure reported by the command if this error was caused by one.
+    pub fn command_error(&self) -> Option<&CommandError> {
+        match self.err.code {
+            ErrorCode::Command(ref e) => Some(e),
+            _ => None,
+        }
+    }
+
     pub(crate) fn io(e: io::Error, pos: Option<usize>) -> Self {
         Self {
             err: Box::new(ErrorImpl {
@@ -67,6 +94,14 @@ impl Error {
         }
     }
 
+    pub(crate) fn command(e: CommandError) -> Self {
+        Self::data(ErrorCode::Command(e), None, None)
+    }
+
+    pub(crate) fn timeout(command: &str) -> Self {
+        Self::data(ErrorCode::Timeout(command.to_string()), None, None)
+    }
+
     pub(crate) fn data(code: ErrorCode, input: Option<u8>, pos: Option<usize>) -> Self {
         Self {
             err: Box::new(ErrorImpl { code, input, pos }),
@@ -76,6 +111,7 @@ impl Error {
 
 /// Categorizes the cause of a `serde_vici::Error`.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
 pub enum Category {
     /// The error was caused by a failure to read or write bytes on an IO stream.
     Io,
@@ -85,6 +121,12 @@ pub enum Category {
 
     /// The error was caused by prematurely reaching the end of the input data.
     Eof,
+
+    /// The error was caused by a command reporting its failure.
+    Command,
+
+    /// The error was caused by a command not completing in time.
+    Timeout,
 }
 
 impl From<io::Error> for Error {
@@ -98,6 +140,8 @@ impl From<Error> for io::Error {
     ///
     /// VICI data errors are turned into `InvalidData` IO errors.
     /// EOF errors are turned into `UnexpectedEof` IO errors.
+    /// Command errors are turned into `Other` IO errors.
+    /// Timeout errors are turned into `TimedOut` IO errors.
     fn from(e: Error) -> Self {
         match e.classify() {
             Category::Io => {
@@ -109,6 +153,8 @@ impl From<Error> for io::Error {
             },
             Category::Data => io::Error::new(io::ErrorKind::InvalidData, e),
             Category::Eof => io::Error::new(io::ErrorKind::UnexpectedEof, e),
+            Category::Command => io::Error::other(e),
+            Category::Timeout => io::Error::new(io::ErrorKind::TimedOut, e),
         }
     }
 }
@@ -120,6 +166,9 @@ pub(crate) enum ErrorCode {
     /// Catchall for invalid data error messages.
     Message(String),
 
+    /// EOF while parsing a packet.
+    EofWhileParsingPacket,
+
     /// EOF while parsing an element type.
     EofWhileParsingElementType,
 
@@ -131,6 +180,12 @@ pub(crate) enum ErrorCode {
 
     /// Invalid unicode code point.
     InvalidUnicodeCodePoint,
+
+    /// A command reported its failure.
+    Command(CommandError),
+
+    /// A command did not complete in time.
+    Timeout(String),
 }
 
 impl Display for ErrorCode {
@@ -138,10 +193,13 @@ impl Display for ErrorCode {
         match *self {
             ErrorCode::Io(ref err) => Display::fmt(err, f),
             ErrorCode::Message(ref msg) => f.write_str(msg),
+            ErrorCode::EofWhileParsingPacket => f.write_str("EOF while parsing packet"),
             ErrorCode::EofWhileParsingElementType => f.write_str("EOF while parsing element type"),
             ErrorCode::EofWhileParsingKey => f.write_str("EOF while parsing key"),
             ErrorCode::EofWhileParsingValue => f.write_str("EOF while parsing value"),
             ErrorCode::InvalidUnicodeCodePoint => f.write_str("invalid unicode code point"),
+            ErrorCode::Command(ref err) => Display::fmt(err, f),
+            ErrorCode::Timeout(ref command) => write!(f, "command timed out: {command}"),
         }
     }
 }
@@ -150,6 +208,7 @@ impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self.err.code {
             ErrorCode::Io(ref err) => Some(err),
+            ErrorCode::Command(ref err) => Some(err),
             _ => None,
         }
     }
@@ -0,0 +1,662 @@
+//! Break down the bytes of VICI packets and messages to find out where they are malformed.
+//!
+//! Each field is listed with its offset, its bytes, and what it decodes to. Decoding stops at the first malformed byte, which is
+//! flagged together with the error.
+//!
+//! ```text
+//! 00000000  00 00 00 14                 length = 20
+//! 00000004  00                          packet type = CMD_REQUEST
+//! 00000005  07 76 65 72 73 69 6f 6e     name (7) = "version"
+//! 0000000d  03                          KEY_VALUE
+//! 0000000e  02 69 64                    key (2) = "id"
+//! 00000011  00                          ^ EOF while parsing value at position 17
+//! ```
+//!
+//! When deserializing a message fails, a [`Diagnostic`] shows the bytes around the error with the offending element highlighted,
+//! along with the path of keys enclosing it.
+
+use std::fmt::{self, Display, Write};
+
+use crate::{
+    error::{Error, ErrorCode, Result},
+    packet::PacketType,
+    read::{Read, SliceRead},
+    ElementType,
+};
+
+/// The number of bytes of a field shown in a breakdown.
+const MAX_SHOWN_BYTES: usize = 8;
+
+/// The number of bytes on each line of a hexdump.
+const LINE_BYTES: usize = 16;
+
+/// The number of bytes shown before and after the offending element in a diagnostic.
+const CONTEXT_BYTES: usize = 32;
+
+/// The number of bytes of the offending element highlighted in a diagnostic.
+const MAX_HIGHLIGHTED_BYTES: usize = 64;
+
+/// A breakdown of the fields of VICI packets or a VICI message.
+///
+/// # Example
+///
+/// ```
+/// use serde_vici::inspect::Dump;
+///
+/// #[rustfmt::skip]
+/// let message = [
+///     // key = value
+///     3, 3, b'k', b'e', b'y', 0, 5, b'v', b'a', b'l', b'u', b'e',
+///     // invalid element type
+///     9,
+/// ];
+///
+/// let dump = Dump::message(&message);
+/// assert_eq!(dump.fields().len(), 3);
+/// assert_eq!(dump.error().unwrap().to_string(), "invalid element type 0x9 at position 12");
+/// print!("{dump}");
+/// ```
+#[derive(Debug)]
+pub struct Dump<'a> {
+    input: &'a [u8],
+    fields: Vec<Field>,
+    error: Option<Error>,
+}
+
+/// A single field of a packet or message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Field {
+    /// The zero-based offset of the field in the input.
+    pub offset: usize,
+
+    /// The number of bytes of the field, including its length prefix.
+    pub len: usize,
+
+    /// The number of sections and lists the field is nested in.
+    pub depth: usize,
+
+    /// What the field decodes to.
+    pub description: String,
+}
+
+impl<'a> Dump<'a> {
+    /// Breaks down a VICI message without any packet header.
+    pub fn message(input: &'a [u8]) -> Self {
+        let mut dump = Self::new(input);
+        dump.error = dump.parse_message(0, input.len()).err();
+        dump
+    }
+
+    /// Breaks down consecutive VICI packets, each prefixed by its length.
+    pub fn packets(input: &'a [u8]) -> Self {
+        let mut dump = Self::new(input);
+        let mut pos = 0;
+        while pos < input.len() {
+            match dump.parse_packet(pos) {
+                Ok(end) => pos = end,
+                Err(e) => {
+                    dump.error = Some(e);
+                    break;
+                },
+            }
+        }
+        dump
+    }
+
+    /// Returns the bytes being broken down.
+    pub fn input(&self) -> &'a [u8] {
+        self.input
+    }
+
+    /// Returns the fields decoded before the error if any.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Returns the error at which decoding stopped.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    fn new(input: &'a [u8]) -> Self {
+        let fields = vec![];
+        let error = None;
+        Self { input, fields, error }
+    }
+
+    fn push(&mut self, offset: usize, len: usize, depth: usize, description: String) {
+        self.fields.push(Field {
+            offset,
+            len,
+            depth,
+            description,
+        });
+    }
+
+    /// Parses the packet at the given offset and returns the offset of the next one.
+    fn parse_packet(&mut self, start: usize) -> Result<usize> {
+        let input = self.input;
+        let Some(len) = input.get(start..start + 4) else {
+            return Err(Error::data(ErrorCode::EofWhileParsingPacket, None, Some(input.len())));
+        };
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        self.push(start, 4, 0, format!("length = {len}"));
+
+        // A truncated packet is broken down as far as it goes.
+        let end = start + 4 + len;
+        let available = end.min(input.len());
+
+        let pos = start + 4;
+        let Some(&packet_type) = input[..available].get(pos) else {
+            return Err(Error::data(ErrorCode::EofWhileParsingPacket, None, Some(pos)));
+        };
+        let packet_type = PacketType::try_from(packet_type)
+            .map_err(|e| Error::data(ErrorCode::Message("invalid packet type".into()), Some(e.number), Some(pos)))?;
+        self.push(pos, 1, 0, format!("packet type = {packet_type}"));
+
+        let mut pos = pos + 1;
+        if packet_type.is_named() {
+            let mut scratch = vec![];
+            let mut read = SliceRead::with_position(&input[..available], pos);
+            let name = read
+                .parse_key(&mut scratch)
+                .map_err(|e| eof_as(e, ErrorCode::EofWhileParsingPacket))?;
+            self.push(pos, 1 + name.len(), 0, format!("name ({}) = {:?}", name.len(), &*name));
+            pos = read.position();
+        }
+
+        self.parse_message(pos, available)?;
+        if end > input.len() {
+            return Err(Error::data(ErrorCode::EofWhileParsingPacket, None, Some(input.len())));
+        }
+        Ok(end)
+    }
+
+    /// Parses the message between the given offsets.
+    fn parse_message(&mut self, start: usize, end: usize) -> Result<()> {
+        let mut read = SliceRead::with_position(&self.input[..end], start);
+        let mut depth = 0;
+        let mut in_list = false;
+
+        while read.position() < end {
+            let offset = read.position();
+            let element_type = read.parse_element_type()?;
+            match element_type {
+                ElementType::SectionStart if !in_list => {
+                    self.push(offset, 1, depth, "SECTION_START".to_string());
+                    self.parse_key(&mut read, depth)?;
+                    depth += 1;
+                },
+                ElementType::SectionEnd if !in_list && depth > 0 => {
+                    depth -= 1;
+                    self.push(offset, 1, depth, "SECTION_END".to_string());
+                },
+                ElementType::KeyValue if !in_list => {
+                    self.push(offset, 1, depth, "KEY_VALUE".to_string());
+                    self.parse_key(&mut read, depth)?;
+                    self.parse_value(&mut read, depth)?;
+                },
+                ElementType::ListStart if !in_list => {
+                    self.push(offset, 1, depth, "LIST_START".to_string());
+                    self.parse_key(&mut read, depth)?;
+                    depth += 1;
+                    in_list = true;
+                },
+                ElementType::ListItem if in_list => {
+                    self.push(offset, 1, depth, "LIST_ITEM".to_string());
+                    self.parse_value(&mut read, depth)?;
+                },
+                ElementType::ListEnd if in_list => {
+                    depth -= 1;
+                    in_list = false;
+                    self.push(offset, 1, depth, "LIST_END".to_string());
+                },
+                _ => {
+                    return Err(Error::data(
+                        ErrorCode::Message("unexpected element type".into()),
+                        Some(element_type as u8),
+                        Some(offset),
+                    ));
+                },
+            }
+        }
+
+        if depth > 0 {
+            return Err(Error::data(ErrorCode::EofWhileParsingElementType, None, Some(end)));
+        }
+        Ok(())
+    }
+
+    fn parse_key(&mut self, read: &mut SliceRead<'a>, depth: usize) -> Result<()> {
+        let offset = read.position();
+        let mut scratch = vec![];
+        let key = read.parse_key(&mut scratch)?;
+        self.push(
+            offset,
+            read.position() - offset,
+            depth,
+            format!("key ({}) = {:?}", key.len(), &*key),
+        );
+        Ok(())
+    }
+
+    fn parse_value(&mut self, read: &mut SliceRead<'a>, depth: usize) -> Result<()> {
+        let offset = read.position();
+        let mut scratch = vec![];
+        let value = read.parse_value_raw(&mut scratch)?;
+        let description = match std::str::from_utf8(&value) {
+            Ok(s) => format!("value ({}) = {s:?}", value.len()),
+            Err(_) => format!("value ({}) = 0x{}", value.len(), hex(&value, "")),
+        };
+        self.push(offset, read.position() - offset, depth, description);
+        Ok(())
+    }
+}
+
+impl Display for Dump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = MAX_SHOWN_BYTES * 3 + 2;
+        for field in &self.fields {
+            let bytes = &self.input[field.offset..field.offset + field.len];
+            let mut shown = hex(&bytes[..bytes.len().min(MAX_SHOWN_BYTES)], " ");
+            if bytes.len() > MAX_SHOWN_BYTES {
+                shown.push_str(" ..");
+            }
+            let indent = "  ".repeat(field.depth);
+            writeln!(f, "{:08x}  {shown:width$}  {indent}{}", field.offset, field.description)?;
+        }
+
+        if let Some(error) = &self.error {
+            match error.position() {
+                Some(pos) => {
+                    let byte = self.input.get(pos).map_or("--".to_string(), |b| format!("{b:02x}"));
+                    writeln!(f, "{pos:08x}  {byte:width$}  ^ {error}")?;
+                },
+                None => writeln!(f, "error: {error}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A renderer of an error that occurred while deserializing a VICI message, showing where in the input it occurred.
+///
+/// # Example
+///
+/// ```
+/// use serde_vici::inspect::Diagnostic;
+/// use std::collections::BTreeMap;
+///
+/// #[rustfmt::skip]
+/// let input = [
+///     // section1
+///     1, 8, b's', b'e', b'c', b't', b'i', b'o', b'n', b'1',
+///     // key1 = value1 with invalid unicode
+///     3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', 0xff, b'e', b'1',
+///     // section1 end
+///     2,
+/// ];
+///
+/// let err = serde_vici::from_slice::<BTreeMap<String, BTreeMap<String, String>>>(&input).unwrap_err();
+/// assert_eq!(
+///     Diagnostic::new(&input, &err).to_string(),
+///     concat!(
+///         "invalid unicode code point 0xff at position 21\n",
+///         "in section1.key1\n",
+///         "00000000  01 08 73 65 63 74 69 6f  6e 31 03 04 6b 65 79 31  |..section1..key1|\n",
+///         "                                         ^^ ^^ ^^ ^^ ^^ ^^\n",
+///         "00000010  00 06 76 61 6c ff 65 31  02                       |..val.e1.|\n",
+///         "          ^^ ^^ ^^ ^^ ^^ ^^ ^^ ^^\n",
+///     )
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Diagnostic<'a> {
+    input: &'a [u8],
+    error: &'a Error,
+}
+
+/// The element in which an error occurred.
+struct Location {
+    start: usize,
+    end: usize,
+    path: String,
+}
+
+impl<'a> Diagnostic<'a> {
+    /// Creates a diagnostic of an error that occurred while deserializing the given input.
+    pub fn new(input: &'a [u8], error: &'a Error) -> Self {
+        Self { input, error }
+    }
+
+    /// Finds the element that contains the given position along with the path of keys enclosing it.
+    fn locate(&self, pos: usize) -> Location {
+        let mut read = SliceRead::new(self.input);
+        let mut scratch = vec![];
+        let mut path: Vec<String> = vec![];
+        let mut items = 0;
+        let mut last = None;
+
+        // Errors detected by the deserializer are reported past the element type that was unexpected, so the offending element is
+        // the last one starting before the position, unless the byte at the position is not an element type at all.
+        while read.position() < pos.min(self.input.len()) {
+            let start = read.position();
+            let Ok(element_type) = read.parse_element_type() else {
+                break;
+            };
+
+            scratch.clear();
+            let mut keys = path.clone();
+            let complete = match element_type {
+                ElementType::SectionStart | ElementType::ListStart => {
+                    read.parse_key(&mut scratch).map(|key| keys.push(key.to_string())).is_ok()
+                },
+                ElementType::KeyValue => {
+                    read.parse_key(&mut scratch).map(|key| keys.push(key.to_string())).is_ok() && read.parse_value_raw(&mut scratch).is_ok()
+                },
+                ElementType::ListItem => {
+                    if let Some(list) = keys.last_mut() {
+                        let _ = write!(list, "[{items}]");
+                    }
+                    read.parse_value_raw(&mut scratch).is_ok()
+                },
+                ElementType::SectionEnd | ElementType::ListEnd => true,
+            };
+
+            let end = if complete { read.position() } else { self.input.len() };
+            last = Some(Location {
+                start,
+                end,
+                path: keys.join("."),
+            });
+            if !complete {
+                break;
+            }
+
+            match element_type {
+                ElementType::SectionStart | ElementType::ListStart => {
+                    path = keys;
+                    items = 0;
+                },
+                ElementType::SectionEnd | ElementType::ListEnd => {
+                    path.pop();
+                },
+                ElementType::ListItem => items += 1,
+                ElementType::KeyValue => {},
+            }
+        }
+
+        let is_element_type = self.input.get(pos).is_some_and(|&b| ElementType::try_from(b).is_ok());
+        match last {
+            Some(last) if is_element_type || pos < last.end => last,
+            _ => Location {
+                start: pos,
+                end: pos + 1,
+                path: path.join("."),
+            },
+        }
+    }
+}
+
+impl Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.error)?;
+        let Some(pos) = self.error.position() else {
+            return Ok(());
+        };
+
+        let location = self.locate(pos);
+        if location.path.is_empty() {
+            writeln!(f, "at the top level")?;
+        } else {
+            writeln!(f, "in {}", location.path)?;
+        }
+
+        let highlighted = location.start..location.end.min(location.start + MAX_HIGHLIGHTED_BYTES);
+        let first = location.start.saturating_sub(CONTEXT_BYTES) / LINE_BYTES;
+        let last = (highlighted.end + CONTEXT_BYTES)
+            .min(self.input.len().max(pos + 1))
+            .div_ceil(LINE_BYTES);
+        for line in first..last.max(first + 1) {
+            let offset = line * LINE_BYTES;
+            let bytes = self.input.get(offset..).unwrap_or_default();
+            let bytes = &bytes[..bytes.len().min(LINE_BYTES)];
+
+            let mut hex = String::new();
+            let mut marker = String::new();
+            for i in 0..LINE_BYTES {
+                let separator = if i == LINE_BYTES / 2 { "  " } else { " " };
+                hex.push_str(separator);
+                marker.push_str(separator);
+                match bytes.get(i) {
+                    Some(b) => {
+                        let _ = write!(hex, "{b:02x}");
+                    },
+                    None => hex.push_str("  "),
+                }
+                marker.push_str(if highlighted.contains(&(offset + i)) { "^^" } else { "  " });
+            }
+            let ascii: String = bytes
+                .iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+
+            writeln!(f, "{offset:08x} {hex}  |{ascii}|")?;
+            if marker.contains('^') {
+                writeln!(f, "{:9}{}", "", marker.trim_end())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Turns an EOF error of the reader into the given error code at the same position.
+fn eof_as(e: Error, code: ErrorCode) -> Error {
+    if e.is_eof() {
+        Error::data(code, None, e.position())
+    } else {
+        e
+    }
+}
+
+fn hex(bytes: &[u8], separator: &str) -> String {
+    bytes.iter().enumerate().fold(String::new(), |mut s, (i, b)| {
+        if i > 0 {
+            s.push_str(separator);
+        }
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::packet::{encode, Packet};
+
+    #[test]
+    fn dump_message() {
+        #[rustfmt::skip]
+        let input = vec![
+            // key1 = value1
+            3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+            // section1
+            1, 8, b's', b'e', b'c', b't', b'i', b'o', b'n', b'1',
+            // list1
+            4, 5, b'l', b'i', b's', b't', b'1',
+            // item1
+            5, 0, 2, 0xff, 0xfe,
+            // list1 end
+            6,
+            // section1 end
+            2,
+        ];
+
+        let dump = Dump::message(&input);
+        assert!(dump.error().is_none());
+        assert_eq!(
+            dump.to_string(),
+            concat!(
+                "00000000  03                          KEY_VALUE\n",
+                "00000001  04 6b 65 79 31              key (4) = \"key1\"\n",
+                "00000006  00 06 76 61 6c 75 65 31     value (6) = \"value1\"\n",
+                "0000000e  01                          SECTION_START\n",
+                "0000000f  08 73 65 63 74 69 6f 6e ..  key (8) = \"section1\"\n",
+                "00000018  04                            LIST_START\n",
+                "00000019  05 6c 69 73 74 31             key (5) = \"list1\"\n",
+                "0000001f  05                              LIST_ITEM\n",
+                "00000020  00 02 ff fe                     value (2) = 0xfffe\n",
+                "00000024  06                            LIST_END\n",
+                "00000025  02                          SECTION_END\n",
+            )
+        );
+    }
+
+    #[test]
+    fn dump_message_error() {
+        #[rustfmt::skip]
+        let input = vec![
+            // section1
+            1, 8, b's', b'e', b'c', b't', b'i', b'o', b'n', b'1',
+            // item outside of a list
+            5, 0, 1, b'a',
+        ];
+
+        let dump = Dump::message(&input);
+        assert_eq!(dump.fields().len(), 2);
+        assert_eq!(
+            dump.to_string(),
+            concat!(
+                "00000000  01                          SECTION_START\n",
+                "00000001  08 73 65 63 74 69 6f 6e ..  key (8) = \"section1\"\n",
+                "0000000a  05                          ^ unexpected element type 0x5 at position 10\n",
+            )
+        );
+
+        #[rustfmt::skip]
+        let input = vec![
+            // key with invalid unicode
+            3, 3, b'k', 0xc3, b'y',
+        ];
+
+        let dump = Dump::message(&input);
+        assert_eq!(
+            dump.to_string(),
+            concat!(
+                "00000000  03                          KEY_VALUE\n",
+                "00000003  c3                          ^ invalid unicode code point 0xc3 at position 3\n",
+            )
+        );
+
+        #[rustfmt::skip]
+        let input = vec![
+            // section1
+            1, 8, b's', b'e', b'c', b't', b'i', b'o', b'n', b'1',
+        ];
+
+        let dump = Dump::message(&input);
+        assert_eq!(dump.error().unwrap().to_string(), "EOF while parsing element type at position 10");
+    }
+
+    #[test]
+    fn dump_packets() {
+        let mut input = encode(&Packet::request("version", &()).unwrap()).unwrap();
+        input.extend(
+            encode(&Packet::response(&[("daemon", "charon")].into_iter().collect::<std::collections::BTreeMap<_, _>>()).unwrap()).unwrap(),
+        );
+
+        let dump = Dump::packets(&input);
+        assert!(dump.error().is_none());
+        assert_eq!(
+            dump.to_string(),
+            concat!(
+                "00000000  00 00 00 09                 length = 9\n",
+                "00000004  00                          packet type = CMD_REQUEST\n",
+                "00000005  07 76 65 72 73 69 6f 6e     name (7) = \"version\"\n",
+                "0000000d  00 00 00 11                 length = 17\n",
+                "00000011  01                          packet type = CMD_RESPONSE\n",
+                "00000012  03                          KEY_VALUE\n",
+                "00000013  06 64 61 65 6d 6f 6e        key (6) = \"daemon\"\n",
+                "0000001a  00 06 63 68 61 72 6f 6e     value (6) = \"charon\"\n",
+            )
+        );
+
+        let dump = Dump::packets(&input[..input.len() - 3]);
+        assert_eq!(dump.fields().len(), 7);
+        assert_eq!(dump.error().unwrap().to_string(), "EOF while parsing value at position 26");
+
+        #[rustfmt::skip]
+        let input = vec![
+            // length
+            0, 0, 0, 1,
+            // invalid packet type
+            8,
+        ];
+
+        let dump = Dump::packets(&input);
+        assert_eq!(
+            dump.to_string(),
+            concat!(
+                "00000000  00 00 00 01                 length = 1\n",
+                "00000004  08                          ^ invalid packet type 0x8 at position 4\n",
+            )
+        );
+    }
+
+    #[test]
+    fn diagnostic() {
+        #[rustfmt::skip]
+        let input = vec![
+            // list1
+            4, 5, b'l', b'i', b's', b't', b'1',
+            // item1
+            5, 0, 5, b'i', b't', b'e', b'm', b'1',
+            // key = value in a list
+            3, 3, b'k', b'e', b'y', 0, 5, b'v', b'a', b'l', b'u', b'e',
+            // list1 end
+            6,
+        ];
+
+        let err = crate::from_slice::<BTreeMap<String, Vec<String>>>(&input).unwrap_err();
+        assert_eq!(
+            Diagnostic::new(&input, &err).to_string(),
+            concat!(
+                "unexpected element type 0x3 at position 16\n",
+                "in list1.key\n",
+                "00000000  04 05 6c 69 73 74 31 05  00 05 69 74 65 6d 31 03  |..list1...item1.|\n",
+                "                                                        ^^\n",
+                "00000010  03 6b 65 79 00 05 76 61  6c 75 65 06              |.key..value.|\n",
+                "          ^^ ^^ ^^ ^^ ^^ ^^ ^^ ^^  ^^ ^^ ^^\n",
+            )
+        );
+
+        let err = crate::from_slice::<BTreeMap<String, Vec<String>>>(&input[..15]).unwrap_err();
+        assert_eq!(
+            Diagnostic::new(&input[..15], &err).to_string(),
+            concat!(
+                "EOF while parsing element type at position 15\n",
+                "in list1\n",
+                "00000000  04 05 6c 69 73 74 31 05  00 05 69 74 65 6d 31     |..list1...item1|\n",
+                "                                                        ^^\n",
+            )
+        );
+
+        let err = crate::from_slice::<BTreeMap<String, String>>(&[9]).unwrap_err();
+        assert_eq!(
+            Diagnostic::new(&[9], &err).to_string(),
+            concat!(
+                "invalid element type 0x9 at position 0\n",
+                "at the top level\n",
+                "00000000  09                                                |.|\n",
+                "          ^^\n",
+            )
+        );
+
+        let err = crate::from_slice::<BTreeMap<String, u32>>(&input[15..27]).unwrap_err();
+        assert_eq!(Diagnostic::new(&input[15..27], &err).to_string(), "invalid digit found in string\n");
+    }
+}
@@ -78,20 +78,50 @@
 use num_enum::TryFromPrimitive;
 
 #[doc(inline)]
-pub use crate::de::{from_reader, from_slice, Deserializer};
+pub use crate::de::{
+    from_bytes, from_bytes_with, from_reader, from_reader_with, from_slice, from_slice_with, Deserializer, DeserializerOptions,
+};
 #[doc(inline)]
 pub use crate::error::Error;
 #[doc(inline)]
-pub use crate::ser::{to_vec, to_writer, Serializer};
+pub use crate::ser::{
+    serialized_size, serialized_size_with, to_buf, to_bytes, to_vec, to_vec_with, to_writer, Serializer, SerializerOptions,
+};
+#[cfg(feature = "futures-io")]
+#[doc(inline)]
+pub use crate::{de::from_async_reader, ser::to_async_writer};
 
+pub mod client;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod command;
 pub mod de;
 pub mod error;
+pub mod inspect;
+#[cfg(feature = "swanctl")]
+#[doc(hidden)]
+pub mod macros;
+pub mod packet;
+pub mod query;
+pub mod raw;
+#[cfg(feature = "swanctl")]
+pub mod reconcile;
+pub mod record;
 pub mod ser;
+#[cfg(feature = "swanctl")]
+pub mod swanctl;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transport;
 
+#[cfg(feature = "futures-io")]
+mod async_io;
+mod field;
 mod read;
+#[cfg(feature = "swanctl")]
 mod value;
 
-#[derive(Debug, Eq, PartialEq, TryFromPrimitive)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, TryFromPrimitive)]
 #[doc(hidden)]
 #[repr(u8)]
 pub enum ElementType {
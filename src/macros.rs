@@ -0,0 +1,254 @@
+//! Support for the [`vici!`](crate::vici) and [`try_vici!`](crate::try_vici) macros.
+
+use serde::ser::{self, Serialize, SerializeMap, Serializer};
+
+use crate::{
+    error::Result,
+    to_vec,
+    value::{to_value, Section, Value},
+};
+
+/// Constructs an encoded VICI message from a JSON-like literal.
+///
+/// Keys are string literals or parenthesized expressions, and values are nested sections in braces, lists in brackets, or any
+/// expression implementing `Serialize`. Values that serialize to nothing, such as `None`, are omitted as they are by the
+/// [`Serializer`](crate::Serializer).
+///
+/// # Panics
+/// Panics if an interpolated value cannot be serialized, or if an item of a list is not a single value, including an item that
+/// serializes to nothing such as `None`. Use [`try_vici!`](crate::try_vici) to get these failures as an error instead.
+///
+/// # Example
+///
+/// ```
+/// use serde_vici::vici;
+///
+/// let name = "list1";
+/// let items = ["item1", "item2"];
+///
+/// let msg = vici!({
+///     "key1": "value1",
+///     "section1": {
+///         "sub-section": {
+///             "key2": "value2",
+///         },
+///         (name): [items[0], items[1]],
+///     },
+/// });
+///
+/// #[rustfmt::skip]
+/// let expected = vec![
+///     // key1 = value1
+///     3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+///     // section1
+///     1, 8, b's', b'e', b'c', b't', b'i', b'o', b'n', b'1',
+///     // sub-section
+///     1, 11, b's', b'u', b'b', b'-', b's', b'e', b'c', b't', b'i', b'o', b'n',
+///     // key2 = value2
+///     3, 4, b'k', b'e', b'y', b'2', 0, 6, b'v', b'a', b'l', b'u', b'e', b'2',
+///     // sub-section end
+///     2,
+///     // list1
+///     4, 5, b'l', b'i', b's', b't', b'1',
+///     // item1
+///     5, 0, 5, b'i', b't', b'e', b'm', b'1',
+///     // item2
+///     5, 0, 5, b'i', b't', b'e', b'm', b'2',
+///     // list1 end
+///     6,
+///     // section1 end
+///     2,
+/// ];
+/// assert_eq!(msg, expected);
+/// ```
+#[macro_export]
+macro_rules! vici {
+    ({ $($tt:tt)* }) => {
+        $crate::try_vici!({ $($tt)* }).expect("message cannot be serialized as VICI")
+    };
+}
+
+/// Constructs an encoded VICI message from a JSON-like literal, returning an error if it cannot be serialized.
+///
+/// The syntax is the same as for [`vici!`](crate::vici).
+///
+/// # Errors
+/// Fails if an interpolated value cannot be serialized, or if an item of a list is not a single value, including an item that
+/// serializes to nothing such as `None`.
+///
+/// # Example
+///
+/// ```
+/// use serde_vici::try_vici;
+///
+/// let remote: Option<&str> = None;
+///
+/// let msg = try_vici!({ "remote_addrs": [remote] });
+/// assert_eq!(msg.unwrap_err().to_string(), "list items must be single values");
+/// ```
+#[macro_export]
+macro_rules! try_vici {
+    ({ $($tt:tt)* }) => {
+        $crate::macros::build(|| ::std::result::Result::Ok($crate::try_vici!(@section $($tt)*)))
+    };
+
+    (@section $($tt:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut section = $crate::swanctl::Section::new();
+        $crate::try_vici!(@entry section $($tt)*);
+        section
+    }};
+
+    (@entry $section:ident) => {};
+    (@entry $section:ident $key:literal : $($rest:tt)*) => {
+        $crate::try_vici!(@value $section ($key) $($rest)*);
+    };
+    (@entry $section:ident ($key:expr) : $($rest:tt)*) => {
+        $crate::try_vici!(@value $section ($key) $($rest)*);
+    };
+
+    (@value $section:ident ($key:expr) { $($inner:tt)* } $(, $($rest:tt)*)?) => {
+        $section.insert($key, $crate::try_vici!(@section $($inner)*));
+        $crate::try_vici!(@entry $section $($($rest)*)?);
+    };
+    (@value $section:ident ($key:expr) [ $($item:expr),* $(,)? ] $(, $($rest:tt)*)?) => {
+        $section.insert($key, $crate::swanctl::Value::List(vec![$($crate::macros::item(&$item)?),*]));
+        $crate::try_vici!(@entry $section $($($rest)*)?);
+    };
+    (@value $section:ident ($key:expr) $value:expr $(, $($rest:tt)*)?) => {
+        if let Some(value) = $crate::macros::value(&$value)? {
+            $section.insert($key, value);
+        }
+        $crate::try_vici!(@entry $section $($($rest)*)?);
+    };
+}
+
+/// A single entry serialized as a section, to make any value representable as a message.
+struct Entry<'a, T: ?Sized>(&'a T);
+
+impl<T> Serialize for Entry<'_, T>
+where
+    T: ?Sized + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("value", self.0)?;
+        map.end()
+    }
+}
+
+/// Encodes the section built by the macro.
+#[doc(hidden)]
+pub fn build<F>(section: F) -> Result<Vec<u8>>
+where
+    F: FnOnce() -> Result<Section>,
+{
+    to_vec(&section()?)
+}
+
+/// Converts an interpolated value, returning `None` if it is omitted when serialized.
+#[doc(hidden)]
+pub fn value<T>(value: &T) -> Result<Option<Value>>
+where
+    T: ?Sized + Serialize,
+{
+    match to_value(&Entry(value))? {
+        Value::Section(mut section) => Ok(section.remove("value")),
+        _ => unreachable!(),
+    }
+}
+
+/// Converts an interpolated item of a list.
+#[doc(hidden)]
+pub fn item<T>(item: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    match value(item)? {
+        Some(Value::Bytes(v)) => Ok(v),
+        _ => Err(ser::Error::custom("list items must be single values")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_derive::Serialize;
+
+    use crate::{to_vec, value::Section};
+
+    #[test]
+    fn vici_values() {
+        #[derive(Serialize)]
+        struct Child {
+            mode: &'static str,
+        }
+
+        let version = 2;
+        let remote: Option<&str> = None;
+        let actual = vici!({
+            "version": version,
+            "mobike": false,
+            "remote_addrs": remote,
+            "local_addrs": ["192.0.2.1", String::from("192.0.2.2"),],
+            "children": {
+                ("net-".to_string() + "01"): Child { mode: "tunnel" },
+            },
+            "empty": {},
+        });
+
+        #[rustfmt::skip]
+        let expected = vec![
+            // version = 2
+            3, 7, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0, 1, b'2',
+            // mobike = no
+            3, 6, b'm', b'o', b'b', b'i', b'k', b'e', 0, 2, b'n', b'o',
+            // local_addrs
+            4, 11, b'l', b'o', b'c', b'a', b'l', b'_', b'a', b'd', b'd', b'r', b's',
+            // 192.0.2.1
+            5, 0, 9, b'1', b'9', b'2', b'.', b'0', b'.', b'2', b'.', b'1',
+            // 192.0.2.2
+            5, 0, 9, b'1', b'9', b'2', b'.', b'0', b'.', b'2', b'.', b'2',
+            // local_addrs end
+            6,
+            // children
+            1, 8, b'c', b'h', b'i', b'l', b'd', b'r', b'e', b'n',
+            // net-01
+            1, 6, b'n', b'e', b't', b'-', b'0', b'1',
+            // mode = tunnel
+            3, 4, b'm', b'o', b'd', b'e', 0, 6, b't', b'u', b'n', b'n', b'e', b'l',
+            // net-01 end
+            2,
+            // children end
+            2,
+            // empty
+            1, 5, b'e', b'm', b'p', b't', b'y',
+            // empty end
+            2,
+        ];
+        assert_eq!(actual, expected);
+
+        assert_eq!(vici!({}), to_vec(&Section::new()).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "list items must be single values")]
+    fn vici_list_of_sections() {
+        vici!({ "list": [["nested"]] });
+    }
+
+    #[test]
+    fn try_vici_errors() {
+        let version = 2;
+        let remote: Option<&str> = None;
+
+        assert_eq!(try_vici!({ "version": version }).unwrap(), vici!({ "version": 2 }));
+        let err = try_vici!({ "section": { "list": [remote] } }).unwrap_err();
+        assert_eq!(err.to_string(), "list items must be single values");
+        let err = try_vici!({ "list": [["nested"]] }).unwrap_err();
+        assert_eq!(err.to_string(), "list items must be single values");
+    }
+}
@@ -0,0 +1,374 @@
+//! Read and write VICI packets on a transport.
+//!
+//! Every packet on the wire is prefixed by its length as a 32-bit big-endian integer, followed by the packet type, the name of the
+//! command or event for named packet types, and finally the message encoded as VICI.
+
+use std::{fmt, io};
+
+use bytes::BufMut;
+use num_enum::TryFromPrimitive;
+use serde::{de, ser, Deserialize, Serialize};
+
+use crate::{
+    de::from_slice,
+    error::{Error, ErrorCode, Result},
+    ser::to_vec,
+};
+
+/// The maximum size of a packet `charon` accepts, excluding its length prefix.
+pub const MAX_PACKET_SIZE: usize = 512 * 1024;
+
+/// The type of a VICI packet.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize, TryFromPrimitive)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[repr(u8)]
+pub enum PacketType {
+    /// A named request message.
+    CmdRequest = 0,
+
+    /// An unnamed response message for a request.
+    CmdResponse,
+
+    /// An unnamed response if the requested command is unknown.
+    CmdUnknown,
+
+    /// A named event registration request.
+    EventRegister,
+
+    /// A named event deregistration request.
+    EventUnregister,
+
+    /// An unnamed response for successful event (de-)registration.
+    EventConfirm,
+
+    /// An unnamed response if the event (de-)registration failed.
+    EventUnknown,
+
+    /// A named event message.
+    Event,
+}
+
+impl PacketType {
+    /// Returns true if packets of this type carry the name of a command or event.
+    pub fn is_named(self) -> bool {
+        matches!(
+            self,
+            PacketType::CmdRequest | PacketType::EventRegister | PacketType::EventUnregister | PacketType::Event
+        )
+    }
+}
+
+impl fmt::Display for PacketType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PacketType::CmdRequest => "CMD_REQUEST",
+            PacketType::CmdResponse => "CMD_RESPONSE",
+            PacketType::CmdUnknown => "CMD_UNKNOWN",
+            PacketType::EventRegister => "EVENT_REGISTER",
+            PacketType::EventUnregister => "EVENT_UNREGISTER",
+            PacketType::EventConfirm => "EVENT_CONFIRM",
+            PacketType::EventUnknown => "EVENT_UNKNOWN",
+            PacketType::Event => "EVENT",
+        })
+    }
+}
+
+/// A structure representing a single VICI packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Packet {
+    /// The type of this packet.
+    pub packet_type: PacketType,
+
+    /// The name of the command or event, present only for named packet types.
+    pub name: Option<String>,
+
+    /// The message of this packet encoded as VICI.
+    pub body: Vec<u8>,
+}
+
+impl Packet {
+    /// Creates a new packet.
+    pub fn new(packet_type: PacketType, name: Option<String>, body: Vec<u8>) -> Self {
+        Self { packet_type, name, body }
+    }
+
+    /// Creates a command request packet whose message is serialized from the given value.
+    ///
+    /// # Errors
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+    pub fn request<T>(command: &str, value: &T) -> Result<Self>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Ok(Self::new(PacketType::CmdRequest, Some(command.to_string()), to_vec(value)?))
+    }
+
+    /// Creates a command response packet whose message is serialized from the given value.
+    ///
+    /// # Errors
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+    pub fn response<T>(value: &T) -> Result<Self>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Ok(Self::new(PacketType::CmdResponse, None, to_vec(value)?))
+    }
+
+    /// Creates an event packet whose message is serialized from the given value.
+    ///
+    /// # Errors
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+    pub fn event<T>(event: &str, value: &T) -> Result<Self>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Ok(Self::new(PacketType::Event, Some(event.to_string()), to_vec(value)?))
+    }
+
+    /// Deserializes the message of this packet into an instance of type `T`.
+    ///
+    /// # Errors
+    /// Deserialization can fail if the structure of the message does not match the structure expected by `T`.
+    pub fn deserialize<'a, T>(&'a self) -> Result<T>
+    where
+        T: de::Deserialize<'a>,
+    {
+        from_slice(&self.body)
+    }
+}
+
+/// Reads a single VICI packet from the IO stream.
+///
+/// # Errors
+/// Reading can fail if the IO stream fails, the stream ends in the middle of a packet, the packet is larger than
+/// [`MAX_PACKET_SIZE`], or the packet type is unknown.
+pub fn read_packet<R>(reader: &mut R) -> Result<Packet>
+where
+    R: ?Sized + io::Read,
+{
+    let mut len = [0; 4];
+    read_exact(reader, &mut len, 0)?;
+
+    let len = u32::from_be_bytes(len) as usize;
+    check_packet_size(len, MAX_PACKET_SIZE)?;
+    let mut buf = vec![0; len];
+    read_exact(reader, &mut buf, 4)?;
+
+    decode(&buf)
+}
+
+/// Writes a single VICI packet into the IO stream.
+///
+/// # Errors
+/// Writing can fail if the IO stream fails, the name is missing for a named packet type, or the packet is too large to be encoded.
+pub fn write_packet<W>(writer: &mut W, packet: &Packet) -> Result<()>
+where
+    W: ?Sized + io::Write,
+{
+    let buf = encode(packet)?;
+    writer.write_all(&buf)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Encodes the packet including its length prefix.
+pub(crate) fn encode(packet: &Packet) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(encoded_len(packet)?);
+    encode_into(packet, &mut buf)?;
+    Ok(buf)
+}
+
+/// Returns the length of the encoded packet including its length prefix.
+pub(crate) fn encoded_len(packet: &Packet) -> Result<usize> {
+    let (len, _) = header(packet)?;
+    Ok(4 + len as usize)
+}
+
+/// Encodes the packet including its length prefix into the buffer.
+pub(crate) fn encode_into<B>(packet: &Packet, buf: &mut B) -> Result<()>
+where
+    B: BufMut,
+{
+    let (len, name) = header(packet)?;
+    buf.put_u32(len);
+    buf.put_u8(packet.packet_type as u8);
+    if let Some(name) = name {
+        buf.put_u8(name.len() as u8);
+        buf.put_slice(name);
+    }
+    buf.put_slice(&packet.body);
+    Ok(())
+}
+
+fn header(packet: &Packet) -> Result<(u32, Option<&[u8]>)> {
+    let name = match (packet.packet_type.is_named(), &packet.name) {
+        (true, Some(name)) if name.len() <= u8::MAX as usize => Some(name.as_bytes()),
+        (true, Some(_)) => return Err(Error::data(ErrorCode::Message("packet name too long".into()), None, None)),
+        (true, None) => return Err(Error::data(ErrorCode::Message("packet name missing".into()), None, None)),
+        (false, _) => None,
+    };
+
+    let len = 1 + name.map_or(0, |name| 1 + name.len()) + packet.body.len();
+    let len = u32::try_from(len).map_err(|_| Error::data(ErrorCode::Message("packet too large".into()), None, None))?;
+    Ok((len, name))
+}
+
+/// Decodes the packet without its length prefix.
+pub(crate) fn decode(buf: &[u8]) -> Result<Packet> {
+    let (&packet_type, rest) = buf
+        .split_first()
+        .ok_or_else(|| Error::data(ErrorCode::EofWhileParsingPacket, None, Some(4)))?;
+
+    let packet_type = PacketType::try_from(packet_type)
+        .map_err(|e| Error::data(ErrorCode::Message("invalid packet type".into()), Some(e.number), Some(4)))?;
+
+    let (name, body) = if packet_type.is_named() {
+        let (&len, rest) = rest
+            .split_first()
+            .ok_or_else(|| Error::data(ErrorCode::EofWhileParsingPacket, None, Some(5)))?;
+
+        let len = len as usize;
+        if rest.len() < len {
+            return Err(Error::data(ErrorCode::EofWhileParsingPacket, None, Some(buf.len() + 4)));
+        }
+
+        let (name, body) = rest.split_at(len);
+        let name = String::from_utf8(name.to_vec()).map_err(|e| {
+            let valid_up_to = e.utf8_error().valid_up_to();
+            Error::data(
+                ErrorCode::InvalidUnicodeCodePoint,
+                name.get(valid_up_to).copied(),
+                Some(6 + valid_up_to),
+            )
+        })?;
+        (Some(name), body)
+    } else {
+        (None, rest)
+    };
+
+    Ok(Packet::new(packet_type, name, body.to_vec()))
+}
+
+/// Fails if a packet of the given size, excluding its length prefix, exceeds the maximum.
+pub(crate) fn check_packet_size(len: usize, max: usize) -> Result<()> {
+    if len > max {
+        let msg = format!("frame of {len} bytes exceeds the maximum of {max} bytes");
+        return Err(Error::data(ErrorCode::Message(msg), None, None));
+    }
+    Ok(())
+}
+
+fn read_exact<R>(reader: &mut R, buf: &mut [u8], offset: usize) -> Result<()>
+where
+    R: ?Sized + io::Read,
+{
+    let mut pos = 0;
+    while pos < buf.len() {
+        match reader.read(&mut buf[pos..]) {
+            Ok(0) => return Err(Error::data(ErrorCode::EofWhileParsingPacket, None, Some(offset + pos))),
+            Ok(n) => pos += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(Error::io(e, Some(offset + pos))),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[test]
+    fn write_request() {
+        #[derive(Serialize)]
+        struct Request {
+            ike: String,
+        }
+
+        let packet = Packet::request("terminate", &Request { ike: "gw".to_string() }).unwrap();
+
+        let mut actual = vec![];
+        write_packet(&mut actual, &packet).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            actual,
+            vec![
+                // length
+                0, 0, 0, 20,
+                // CMD_REQUEST
+                0,
+                // terminate
+                9, b't', b'e', b'r', b'm', b'i', b'n', b'a', b't', b'e',
+                // ike = gw
+                3, 3, b'i', b'k', b'e', 0, 2, b'g', b'w',
+            ]
+        );
+    }
+
+    #[test]
+    fn read_response() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct Response<'a> {
+            success: bool,
+            errmsg: Option<&'a str>,
+        }
+
+        #[rustfmt::skip]
+        let data: &[_] = &[
+            // length
+            0, 0, 0, 15,
+            // CMD_RESPONSE
+            1,
+            // success = yes
+            3, 7, b's', b'u', b'c', b'c', b'e', b's', b's', 0, 3, b'y', b'e', b's',
+        ];
+
+        let packet = read_packet(&mut &*data).unwrap();
+        assert_eq!(packet.packet_type, PacketType::CmdResponse);
+        assert_eq!(packet.name, None);
+        assert_eq!(
+            packet.deserialize::<Response>().unwrap(),
+            Response {
+                success: true,
+                errmsg: None,
+            }
+        );
+    }
+
+    #[test]
+    fn read_eof() {
+        #[rustfmt::skip]
+        let data: &[_] = &[
+            // length
+            0, 0, 0, 17,
+            // CMD_RESPONSE
+            1,
+        ];
+
+        let err = read_packet(&mut &*data).unwrap_err();
+        assert!(err.is_eof());
+        assert_eq!(err.position(), Some(5));
+    }
+
+    #[test]
+    fn read_too_large() {
+        let data: &[_] = &[0xff, 0xff, 0xff, 0xff, 1];
+
+        let err = read_packet(&mut &*data).unwrap_err();
+        assert!(err.is_data());
+        assert_eq!(err.to_string(), "frame of 4294967295 bytes exceeds the maximum of 524288 bytes");
+    }
+
+    #[test]
+    fn read_invalid_packet_type() {
+        let data: &[_] = &[0, 0, 0, 1, 8];
+
+        let err = read_packet(&mut &*data).unwrap_err();
+        assert!(err.is_data());
+        assert_eq!(err.to_string(), "invalid packet type 0x8 at position 4");
+    }
+}
@@ -0,0 +1,333 @@
+//! Extract values from encoded VICI messages by their path, without deserializing the whole message.
+//!
+//! A path is a dotted sequence of keys, where `*` matches any key, and a list can be followed by `[index]` or `[*]` to select its
+//! items. For example, `*.child-sas.*.bytes-in` matches the inbound byte count of every CHILD_SA of every IKE_SA reported by
+//! `list-sas`, and `*.local_addrs[0]` the first local address of every connection reported by `list-conns`.
+//!
+//! Matches borrow their bytes from the message as [`RawValue`]s: a path ending at a key/value pair or a list item matches its value,
+//! one ending at a section or a list matches its encoded elements.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    error::{Error, ErrorCode, Result},
+    raw::{RawSection, RawValue},
+    read::{scan, Read, Reference, SliceRead, Until},
+    ElementType,
+};
+
+/// A parsed path into a VICI message.
+///
+/// # Example
+///
+/// ```
+/// use serde_vici::{query::Path, raw::RawValue};
+///
+/// #[rustfmt::skip]
+/// let message = [
+///     // gw-01
+///     1, 5, b'g', b'w', b'-', b'0', b'1',
+///     // child-sas
+///     1, 9, b'c', b'h', b'i', b'l', b'd', b'-', b's', b'a', b's',
+///     // net-1
+///     1, 5, b'n', b'e', b't', b'-', b'1',
+///     // bytes-in = 1024
+///     3, 8, b'b', b'y', b't', b'e', b's', b'-', b'i', b'n', 0, 4, b'1', b'0', b'2', b'4',
+///     // net-1 end
+///     2,
+///     // child-sas end
+///     2,
+///     // gw-01 end
+///     2,
+/// ];
+///
+/// let path: Path = "*.child-sas.*.bytes-in".parse().unwrap();
+/// let matches = path.find(&message).unwrap();
+/// assert_eq!(matches[0].path, "gw-01.child-sas.net-1.bytes-in");
+/// assert_eq!(matches[0].value, RawValue::Value(b"1024"));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+/// A value matching a path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Match<'a> {
+    /// The concrete path of the value, with wildcards replaced by the keys and indexes they matched.
+    pub path: String,
+
+    /// The encoded value.
+    pub value: RawValue<'a>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Segment {
+    Key(Option<String>),
+    Item(Option<usize>),
+}
+
+/// Returns the values of a message matching the given path.
+///
+/// # Errors
+/// Fails if the path is invalid, or if the message is malformed up to the last match.
+pub fn query<'a>(input: &'a [u8], path: &str) -> Result<Vec<Match<'a>>> {
+    path.parse::<Path>()?.find(input)
+}
+
+impl Path {
+    /// Returns the values of a message matching this path, in the order they are encoded.
+    ///
+    /// # Errors
+    /// Fails if the message is malformed up to the last match.
+    pub fn find<'a>(&self, input: &'a [u8]) -> Result<Vec<Match<'a>>> {
+        let mut finder = Finder {
+            read: SliceRead::new(input),
+            keys: vec![],
+            matches: vec![],
+        };
+        finder.section(&self.segments, true)?;
+        Ok(finder.matches)
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::data(ErrorCode::Message(format!("invalid path {s:?}")), None, None);
+
+        let mut segments = vec![];
+        for part in s.split('.') {
+            let (key, mut items) = part.split_at(part.find('[').unwrap_or(part.len()));
+            match key {
+                "" => return Err(invalid()),
+                "*" => segments.push(Segment::Key(None)),
+                key => segments.push(Segment::Key(Some(key.to_string()))),
+            }
+
+            while !items.is_empty() {
+                let (index, rest) = items.strip_prefix('[').and_then(|v| v.split_once(']')).ok_or_else(invalid)?;
+                match index {
+                    "*" => segments.push(Segment::Item(None)),
+                    index => segments.push(Segment::Item(Some(index.parse().map_err(|_| invalid())?))),
+                }
+                items = rest;
+            }
+        }
+
+        // Lists only contain values, so nothing can be selected beyond an item.
+        if segments.windows(2).any(|w| matches!(w, [Segment::Item(_), _])) {
+            return Err(invalid());
+        }
+        Ok(Self { segments })
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if i > 0 => write!(f, ".{}", key.as_deref().unwrap_or("*"))?,
+                Segment::Key(key) => f.write_str(key.as_deref().unwrap_or("*"))?,
+                Segment::Item(Some(index)) => write!(f, "[{index}]")?,
+                Segment::Item(None) => f.write_str("[*]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Finder<'a> {
+    read: SliceRead<'a>,
+    keys: Vec<String>,
+    matches: Vec<Match<'a>>,
+}
+
+impl<'a> Finder<'a> {
+    /// Walks the elements of a section, whose end is consumed unless it is the whole message.
+    fn section(&mut self, segments: &[Segment], top_level: bool) -> Result<()> {
+        let mut scratch = vec![];
+        loop {
+            let element_type = match self.read.parse_element_type() {
+                Ok(v) => v,
+                Err(e) if e.is_eof() && top_level => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            let key = match element_type {
+                ElementType::SectionEnd if !top_level => return Ok(()),
+                ElementType::SectionStart | ElementType::KeyValue | ElementType::ListStart => match self.read.parse_key(&mut scratch)? {
+                    Reference::Borrowed(key) => key,
+                    Reference::Copied(_) => unreachable!(),
+                },
+                v => {
+                    return Err(Error::data(
+                        ErrorCode::Message("unexpected element type".into()),
+                        Some(v as u8),
+                        Some(self.read.position()),
+                    ))
+                },
+            };
+
+            let rest = match segments.split_first() {
+                Some((Segment::Key(None), rest)) => Some(rest),
+                Some((Segment::Key(Some(k)), rest)) if k == key => Some(rest),
+                _ => None,
+            };
+
+            self.keys.push(key.to_string());
+            match (element_type, rest) {
+                (ElementType::KeyValue, Some([])) => {
+                    let value = self.parse_value()?;
+                    self.push(RawValue::Value(value));
+                },
+                (ElementType::KeyValue, _) => self.read.skip_value()?,
+                (ElementType::SectionStart, Some([])) => {
+                    let value = self.parse_raw(Until::SectionEnd)?;
+                    self.push(RawValue::Section(RawSection(value)));
+                },
+                (ElementType::SectionStart, Some(rest @ [Segment::Key(_), ..])) => self.section(rest, false)?,
+                (ElementType::SectionStart, _) => scan(&mut self.read, Until::SectionEnd, None).map(drop)?,
+                (ElementType::ListStart, Some([])) => {
+                    let value = self.parse_raw(Until::ListEnd)?;
+                    self.push(RawValue::List(value));
+                },
+                (ElementType::ListStart, Some([Segment::Item(index)])) => self.list(*index)?,
+                (ElementType::ListStart, _) => scan(&mut self.read, Until::ListEnd, None).map(drop)?,
+                _ => unreachable!(),
+            }
+            self.keys.pop();
+        }
+    }
+
+    /// Walks the items of a list, including its end.
+    fn list(&mut self, index: Option<usize>) -> Result<()> {
+        for i in 0.. {
+            match self.read.parse_element_type()? {
+                ElementType::ListItem if index.is_none_or(|index| index == i) => {
+                    let value = self.parse_value()?;
+                    self.keys.push(format!("[{i}]"));
+                    self.push(RawValue::Value(value));
+                    self.keys.pop();
+                },
+                ElementType::ListItem => self.read.skip_value()?,
+                ElementType::ListEnd => break,
+                v => {
+                    return Err(Error::data(
+                        ErrorCode::Message("unexpected element type".into()),
+                        Some(v as u8),
+                        Some(self.read.position()),
+                    ))
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_value(&mut self) -> Result<&'a [u8]> {
+        match self.read.parse_value_raw(&mut vec![])? {
+            Reference::Borrowed(v) => Ok(v),
+            Reference::Copied(_) => unreachable!(),
+        }
+    }
+
+    fn parse_raw(&mut self, until: Until) -> Result<&'a [u8]> {
+        match self.read.parse_raw(until, &mut vec![])? {
+            Reference::Borrowed(v) => Ok(v),
+            Reference::Copied(_) => unreachable!(),
+        }
+    }
+
+    fn push(&mut self, value: RawValue<'a>) {
+        let mut path = String::new();
+        for key in &self.keys {
+            if !path.is_empty() && !key.starts_with('[') {
+                path.push('.');
+            }
+            path.push_str(key);
+        }
+        self.matches.push(Match { path, value });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[rustfmt::skip]
+    const EXAMPLE: &[u8] = &[
+        // key1 = value1
+        3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+        // section1
+        1, 8, b's', b'e', b'c', b't', b'i', b'o', b'n', b'1',
+        // sub-section
+        1, 11, b's', b'u', b'b', b'-', b's', b'e', b'c', b't', b'i', b'o', b'n',
+        // key2 = value2
+        3, 4, b'k', b'e', b'y', b'2', 0, 6, b'v', b'a', b'l', b'u', b'e', b'2',
+        // sub-section end
+        2,
+        // list1
+        4, 5, b'l', b'i', b's', b't', b'1',
+        // item1
+        5, 0, 5, b'i', b't', b'e', b'm', b'1',
+        // item2
+        5, 0, 5, b'i', b't', b'e', b'm', b'2',
+        // list1 end
+        6,
+        // section1 end
+        2,
+    ];
+
+    fn paths(matches: Vec<Match>) -> Vec<(String, RawValue)> {
+        matches.into_iter().map(|m| (m.path, m.value)).collect()
+    }
+
+    #[test]
+    fn query_paths() {
+        assert_eq!(
+            paths(query(EXAMPLE, "key1").unwrap()),
+            vec![("key1".to_string(), RawValue::Value(b"value1"))]
+        );
+        assert_eq!(
+            paths(query(EXAMPLE, "section1.*.key2").unwrap()),
+            vec![("section1.sub-section.key2".to_string(), RawValue::Value(b"value2"))]
+        );
+        assert_eq!(
+            paths(query(EXAMPLE, "*.list1[*]").unwrap()),
+            vec![
+                ("section1.list1[0]".to_string(), RawValue::Value(b"item1")),
+                ("section1.list1[1]".to_string(), RawValue::Value(b"item2")),
+            ]
+        );
+        assert_eq!(
+            paths(query(EXAMPLE, "section1.list1[1]").unwrap()),
+            vec![("section1.list1[1]".to_string(), RawValue::Value(b"item2"))]
+        );
+        assert_eq!(
+            paths(query(EXAMPLE, "section1.*").unwrap()),
+            vec![
+                ("section1.sub-section".to_string(), RawValue::Section(RawSection(&EXAMPLE[37..51]))),
+                ("section1.list1".to_string(), RawValue::List(&EXAMPLE[59..75])),
+            ]
+        );
+        assert_eq!(query(EXAMPLE, "key1.key2").unwrap(), vec![]);
+        assert_eq!(query(EXAMPLE, "section1.list1[2]").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn query_invalid() {
+        for path in ["", "a..b", "a[", "a[x]", "a[0]b", "a[0].b", "a[0][1]", "[0]"] {
+            let err = query(EXAMPLE, path).unwrap_err();
+            assert_eq!(err.to_string(), format!("invalid path {path:?}"));
+        }
+
+        let path: Path = "*.list1[*]".parse().unwrap();
+        assert_eq!(path.to_string(), "*.list1[*]");
+
+        let err = query(&EXAMPLE[..EXAMPLE.len() - 1], "section1.missing").unwrap_err();
+        assert_eq!(err.to_string(), "EOF while parsing element type at position 76");
+    }
+}
@@ -0,0 +1,367 @@
+//! Capture parts of a VICI message as their encoded bytes to forward them unchanged.
+//!
+//! [`RawSection`] and [`RawValue`] borrow the exact bytes of a section, a list, or a value from the input when deserialized with
+//! [`from_slice`](crate::from_slice), without decoding them into Rust values. The [`Serializer`](crate::Serializer) writes them back
+//! verbatim, so that an object reported by `charon` can be passed to another `charon` byte for byte.
+
+use std::fmt;
+
+use serde::{de, ser, Deserialize, Serialize};
+
+use crate::{
+    de::from_slice,
+    error::{Error, Result},
+    read::Reference,
+};
+
+/// The name of the newtype struct through which `RawSection` asks for the elements of a section.
+pub(crate) const RAW_SECTION_TOKEN: &str = "$serde_vici::private::RawSection";
+
+/// The name of the newtype struct through which `RawValue` asks for the elements of a section, the items of a list, or a value.
+pub(crate) const RAW_VALUE_TOKEN: &str = "$serde_vici::private::RawValue";
+
+/// The name of the newtype struct through which the items of a list are written verbatim.
+pub(crate) const RAW_LIST_TOKEN: &str = "$serde_vici::private::RawList";
+
+/// The kind of the element whose bytes are handed out by the deserializer, in the order of the variants of `RawValue`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum RawKind {
+    Section = 0,
+    List = 1,
+    Value = 2,
+}
+
+/// Hands out the bytes of an element to the visitor of a `RawValue` as the newtype variant of its kind.
+pub(crate) struct RawAccess<'de, 'a> {
+    kind: RawKind,
+    raw: Reference<'de, 'a, [u8]>,
+}
+
+impl<'de, 'a> RawAccess<'de, 'a> {
+    pub(crate) fn new(kind: RawKind, raw: Reference<'de, 'a, [u8]>) -> Self {
+        RawAccess { kind, raw }
+    }
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for RawAccess<'de, 'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let value = seed.deserialize(de::IntoDeserializer::<Error>::into_deserializer(self.kind as u32))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for RawAccess<'de, 'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Err(de::Error::invalid_type(de::Unexpected::NewtypeVariant, &"unit variant"))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(de::Unexpected::NewtypeVariant, &"tuple variant"))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(de::Unexpected::NewtypeVariant, &"struct variant"))
+    }
+}
+
+impl<'de, 'a> de::Deserializer<'de> for RawAccess<'de, 'a> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.raw {
+            Reference::Borrowed(v) => visitor.visit_borrowed_bytes(v),
+            Reference::Copied(v) => visitor.visit_bytes(v),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// A section borrowed as its encoded elements, excluding the name and the end of the section.
+///
+/// The elements of a section are encoded the same way as a whole message, so a `RawSection` at the top level captures the entire
+/// input, and its bytes can be deserialized again with [`RawSection::deserialize`]. Since the bytes are borrowed, it cannot be used in
+/// fields marked with `#[serde(flatten)]`, which buffer their contents first.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use serde_vici::raw::RawSection;
+/// use std::collections::BTreeMap;
+///
+/// fn main() -> Result<()> {
+///     #[rustfmt::skip]
+///     let input = vec![
+///         // gw-01
+///         1, 5, b'g', b'w', b'-', b'0', b'1',
+///         // version = 2
+///         3, 7, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0, 1, b'2',
+///         // gw-01 end
+///         2,
+///     ];
+///
+///     let conns: BTreeMap<&str, RawSection> = serde_vici::from_slice(&input)?;
+///     assert_eq!(conns["gw-01"].as_bytes(), &input[7..19]);
+///     assert_eq!(serde_vici::to_vec(&conns)?, input);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RawSection<'a>(pub(crate) &'a [u8]);
+
+/// A section, a list, or a value borrowed as its encoded bytes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RawValue<'a> {
+    /// The encoded elements of a section.
+    Section(RawSection<'a>),
+
+    /// The encoded items of a list, excluding the name and the end of the list.
+    List(&'a [u8]),
+
+    /// A value of a key/value pair.
+    Value(&'a [u8]),
+}
+
+impl<'a> RawSection<'a> {
+    /// Returns the encoded elements of this section.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Deserializes the elements of this section into an instance of type `T`.
+    ///
+    /// # Errors
+    /// Deserialization can fail if the structure of the section does not match the structure expected by `T`.
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        from_slice(self.0)
+    }
+}
+
+/// The bytes of a raw element, written verbatim by the serializer.
+struct Verbatim<'a>(&'a [u8]);
+
+impl Serialize for Verbatim<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for RawSection<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_SECTION_TOKEN, &Verbatim(self.0))
+    }
+}
+
+impl Serialize for RawValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            RawValue::Section(v) => v.serialize(serializer),
+            RawValue::List(v) => serializer.serialize_newtype_struct(RAW_LIST_TOKEN, &Verbatim(v)),
+            RawValue::Value(v) => serializer.serialize_bytes(v),
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawSection<'a> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RawSectionVisitor;
+
+        impl<'de> de::Visitor<'de> for RawSectionVisitor {
+            type Value = RawSection<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a section borrowed from a slice")
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(RawSection(v))
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_SECTION_TOKEN, RawSectionVisitor)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawValue<'a> {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RawValueVisitor;
+
+        impl<'de> de::Visitor<'de> for RawValueVisitor {
+            type Value = RawValue<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a section, a list, or a value borrowed from a slice")
+            }
+
+            fn visit_enum<A>(self, data: A) -> core::result::Result<Self::Value, A::Error>
+            where
+                A: de::EnumAccess<'de>,
+            {
+                use de::VariantAccess;
+
+                let (kind, variant) = data.variant::<u32>()?;
+                match kind {
+                    0 => variant.newtype_variant().map(|v| RawValue::Section(RawSection(v))),
+                    1 => variant.newtype_variant().map(RawValue::List),
+                    2 => variant.newtype_variant().map(RawValue::Value),
+                    _ => Err(de::Error::invalid_value(
+                        de::Unexpected::Unsigned(kind.into()),
+                        &"variant index 0 <= i < 3",
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(RAW_VALUE_TOKEN, RawValueVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use indexmap::IndexMap;
+    use pretty_assertions::assert_eq;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::to_vec;
+
+    #[rustfmt::skip]
+    const EXAMPLE: &[u8] = &[
+        // key1 = value1
+        3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+        // section1
+        1, 8, b's', b'e', b'c', b't', b'i', b'o', b'n', b'1',
+        // sub-section
+        1, 11, b's', b'u', b'b', b'-', b's', b'e', b'c', b't', b'i', b'o', b'n',
+        // key2 = value2
+        3, 4, b'k', b'e', b'y', b'2', 0, 6, b'v', b'a', b'l', b'u', b'e', b'2',
+        // sub-section end
+        2,
+        // list1
+        4, 5, b'l', b'i', b's', b't', b'1',
+        // item1
+        5, 0, 5, b'i', b't', b'e', b'm', b'1',
+        // item2
+        5, 0, 5, b'i', b't', b'e', b'm', b'2',
+        // list1 end
+        6,
+        // section1 end
+        2,
+    ];
+
+    #[test]
+    fn raw_section() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct RootSection<'a> {
+            key1: &'a str,
+            #[serde(borrow)]
+            section1: RawSection<'a>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct MainSection {
+            #[serde(rename = "sub-section")]
+            sub_section: BTreeMap<String, String>,
+            list1: Vec<String>,
+        }
+
+        let actual: RootSection = crate::from_slice(EXAMPLE).unwrap();
+        assert_eq!(actual.key1, "value1");
+        assert_eq!(actual.section1.as_bytes(), &EXAMPLE[24..EXAMPLE.len() - 1]);
+        assert_eq!(
+            actual.section1.deserialize::<MainSection>().unwrap(),
+            MainSection {
+                sub_section: BTreeMap::from([("key2".to_string(), "value2".to_string())]),
+                list1: vec!["item1".to_string(), "item2".to_string()],
+            }
+        );
+        assert_eq!(to_vec(&actual).unwrap(), EXAMPLE);
+
+        let actual: RawSection = crate::from_slice(EXAMPLE).unwrap();
+        assert_eq!(actual.as_bytes(), EXAMPLE);
+        assert_eq!(to_vec(&actual).unwrap(), EXAMPLE);
+
+        let err = crate::from_slice::<IndexMap<&str, RawSection>>(EXAMPLE).unwrap_err();
+        assert_eq!(err.to_string(), "invalid type: value, expected a section borrowed from a slice");
+    }
+
+    #[test]
+    fn raw_value() {
+        let actual: IndexMap<&str, RawValue> = crate::from_slice(EXAMPLE).unwrap();
+        assert_eq!(actual["key1"], RawValue::Value(b"value1"));
+        assert_eq!(actual["section1"], RawValue::Section(RawSection(&EXAMPLE[24..EXAMPLE.len() - 1])));
+        assert_eq!(to_vec(&actual).unwrap(), EXAMPLE);
+
+        let section1: IndexMap<&str, RawValue> = match actual["section1"] {
+            RawValue::Section(v) => v.deserialize().unwrap(),
+            _ => unreachable!(),
+        };
+        assert_eq!(section1["list1"], RawValue::List(&EXAMPLE[59..75]));
+        assert_eq!(to_vec(&section1).unwrap(), &EXAMPLE[24..EXAMPLE.len() - 1]);
+
+        let err = crate::from_slice::<IndexMap<&str, RawValue>>(&EXAMPLE[..EXAMPLE.len() - 1]).unwrap_err();
+        assert_eq!(err.to_string(), "EOF while parsing element type at position 76");
+    }
+
+    #[test]
+    fn raw_value_without_kind() {
+        let deserializer = de::value::BorrowedBytesDeserializer::<Error>::new(b"value1");
+        let err = RawValue::deserialize(deserializer).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type: byte array, expected a section, a list, or a value borrowed from a slice"
+        );
+    }
+}
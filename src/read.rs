@@ -1,5 +1,7 @@
 use std::{collections::VecDeque, io, ops::Deref, str};
 
+use bytes::Bytes;
+
 use crate::{
     error::{Error, ErrorCode},
     ElementType,
@@ -13,6 +15,125 @@ pub trait Read<'de> {
     fn parse_value<'s>(&mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, str>, Error>;
     fn parse_value_raw<'s>(&mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, [u8]>, Error>;
     fn parse_element_type(&mut self) -> Result<ElementType, Error>;
+    fn skip_key(&mut self) -> Result<(), Error>;
+    fn skip_value(&mut self) -> Result<(), Error>;
+
+    fn parse_value_shared(&mut self, scratch: &mut Vec<u8>) -> Result<Bytes, Error> {
+        self.parse_value_raw(scratch).map(|v| Bytes::copy_from_slice(&v))
+    }
+
+    /// Reads the encoded elements up to the given end, which is consumed but not included.
+    fn parse_raw<'s>(&mut self, until: Until, scratch: &'s mut Vec<u8>) -> Result<Reference<'de, 's, [u8]>, Error> {
+        scan(self, until, Some(scratch))?;
+        Ok(Reference::Copied(scratch))
+    }
+}
+
+/// The element that ends the elements being scanned.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Until {
+    SectionEnd,
+    ListEnd,
+    Eof,
+}
+
+/// Reads the elements up to the given end, copying their encoding into `out` if any, and returns the position of the end.
+pub fn scan<'de, R>(read: &mut R, until: Until, out: Option<&mut Vec<u8>>) -> Result<usize, Error>
+where
+    R: ?Sized + Read<'de>,
+{
+    scan_within(read, until, out, 0, None)
+}
+
+/// Reads the elements up to the given end as [`scan`] does, failing if the sections and lists among them are nested deeper than
+/// `max_depth`, given the level of the section or list they are in.
+pub fn scan_within<'de, R>(
+    read: &mut R,
+    until: Until,
+    mut out: Option<&mut Vec<u8>>,
+    level: usize,
+    max_depth: Option<usize>,
+) -> Result<usize, Error>
+where
+    R: ?Sized + Read<'de>,
+{
+    let mut scratch = vec![];
+    let mut depth = 0;
+    let mut in_list = until == Until::ListEnd;
+
+    loop {
+        let pos = read.position();
+        let element_type = match read.parse_element_type() {
+            Ok(v) => v,
+            Err(e) if e.is_eof() && until == Until::Eof && depth == 0 && !in_list => return Ok(pos),
+            Err(e) => return Err(e),
+        };
+
+        // Sections are never nested in lists, so a list is entered one level below the section it is in.
+        let entering = matches!(element_type, ElementType::SectionStart | ElementType::ListStart) && !in_list;
+        if let Some(max_depth) = max_depth.filter(|&max_depth| entering && level + depth + 1 > max_depth) {
+            return Err(Error::data(
+                ErrorCode::Message(format!("nesting exceeds the maximum depth of {max_depth}")),
+                None,
+                Some(read.position()),
+            ));
+        }
+
+        let (key, value) = match element_type {
+            ElementType::SectionStart if !in_list => {
+                depth += 1;
+                (true, false)
+            },
+            ElementType::SectionEnd if !in_list && depth > 0 => {
+                depth -= 1;
+                (false, false)
+            },
+            ElementType::SectionEnd if !in_list && until == Until::SectionEnd => return Ok(pos),
+            ElementType::KeyValue if !in_list => (true, true),
+            ElementType::ListStart if !in_list => {
+                in_list = true;
+                (true, false)
+            },
+            ElementType::ListItem if in_list => (false, true),
+            ElementType::ListEnd if in_list && until == Until::ListEnd => return Ok(pos),
+            ElementType::ListEnd if in_list => {
+                in_list = false;
+                (false, false)
+            },
+            v => {
+                return Err(Error::data(
+                    ErrorCode::Message("unexpected element type".into()),
+                    Some(v as u8),
+                    Some(read.position()),
+                ))
+            },
+        };
+
+        let Some(out) = &mut out else {
+            // Without a copy to make, keys and values are skipped by their length alone.
+            if key {
+                read.skip_key()?;
+            }
+            if value {
+                read.skip_value()?;
+            }
+            continue;
+        };
+
+        out.push(element_type as u8);
+        if key {
+            scratch.clear();
+            let key = read.parse_key(&mut scratch)?;
+            out.push(key.len() as u8);
+            out.extend_from_slice(key.as_bytes());
+        }
+        if value {
+            scratch.clear();
+            let value = read.parse_value_raw(&mut scratch)?;
+            out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            out.extend_from_slice(&value);
+        }
+    }
 }
 
 pub enum Reference<'b, 'c, T>
@@ -48,6 +169,12 @@ pub struct SliceRead<'a> {
     pos: usize,
 }
 
+/// Reads a `Bytes` buffer in the same way as a slice, handing out binary values as slices of the buffer.
+pub struct BytesRead<'a> {
+    bytes: &'a Bytes,
+    read: SliceRead<'a>,
+}
+
 fn key_size(b: Option<&u8>) -> Option<usize> {
     b.map(|&b| b as usize)
 }
@@ -197,6 +324,36 @@ where
         }
     }
 
+    fn skip_key(&mut self) -> Result<(), Error> {
+        loop {
+            if let Some(size) = key_size(self.buf.front()) {
+                if size < self.buf.len() {
+                    self.buf.drain(..1 + size);
+                    self.pos += 1 + size;
+                    return Ok(());
+                }
+            }
+
+            self.fill_buf()?
+                .ok_or_else(|| Error::data(ErrorCode::EofWhileParsingKey, None, Some(self.pos)))?;
+        }
+    }
+
+    fn skip_value(&mut self) -> Result<(), Error> {
+        loop {
+            if let Some(size) = value_size(self.buf.front(), self.buf.get(1)) {
+                if size < self.buf.len() - 1 {
+                    self.buf.drain(..2 + size);
+                    self.pos += 2 + size;
+                    return Ok(());
+                }
+            }
+
+            self.fill_buf()?
+                .ok_or_else(|| Error::data(ErrorCode::EofWhileParsingValue, None, Some(self.pos)))?;
+        }
+    }
+
     fn parse_element_type(&mut self) -> Result<ElementType, Error> {
         loop {
             if let Some(result) = self.buf.pop_front().map(ElementType::try_from) {
@@ -223,7 +380,10 @@ where
 
 impl<'a> SliceRead<'a> {
     pub fn new(slice: &'a [u8]) -> Self {
-        let pos = 0;
+        Self::with_position(slice, 0)
+    }
+
+    pub fn with_position(slice: &'a [u8], pos: usize) -> Self {
         Self { slice, pos }
     }
 
@@ -302,6 +462,28 @@ impl<'a> Read<'a> for SliceRead<'a> {
         Err(Error::data(ErrorCode::EofWhileParsingValue, None, Some(self.pos)))
     }
 
+    fn skip_key(&mut self) -> Result<(), Error> {
+        if let Some(size) = key_size(self.slice.get(self.pos)) {
+            if self.pos + 1 + size <= self.slice.len() {
+                self.pos += 1 + size;
+                return Ok(());
+            }
+        }
+
+        Err(Error::data(ErrorCode::EofWhileParsingKey, None, Some(self.pos)))
+    }
+
+    fn skip_value(&mut self) -> Result<(), Error> {
+        if let Some(size) = value_size(self.slice.get(self.pos), self.slice.get(self.pos + 1)) {
+            if self.pos + 2 + size <= self.slice.len() {
+                self.pos += 2 + size;
+                return Ok(());
+            }
+        }
+
+        Err(Error::data(ErrorCode::EofWhileParsingValue, None, Some(self.pos)))
+    }
+
     fn parse_element_type(&mut self) -> Result<ElementType, Error> {
         if let Some(result) = self.slice.get(self.pos).copied().map(ElementType::try_from) {
             match result {
@@ -321,4 +503,64 @@ impl<'a> Read<'a> for SliceRead<'a> {
 
         Err(Error::data(ErrorCode::EofWhileParsingElementType, None, Some(self.pos)))
     }
+
+    fn parse_raw<'s>(&mut self, until: Until, _scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, [u8]>, Error> {
+        let start = self.pos;
+        let end = scan(self, until, None)?;
+        Ok(Reference::Borrowed(&self.slice[start..end]))
+    }
+}
+
+impl<'a> BytesRead<'a> {
+    pub fn new(bytes: &'a Bytes) -> Self {
+        let read = SliceRead::new(bytes);
+        Self { bytes, read }
+    }
+}
+
+impl<'a> Read<'a> for BytesRead<'a> {
+    fn position(&self) -> usize {
+        self.read.position()
+    }
+
+    fn peek_key(&mut self) -> Result<usize, Error> {
+        self.read.peek_key()
+    }
+
+    fn peek_value(&mut self) -> Result<usize, Error> {
+        self.read.peek_value()
+    }
+
+    fn parse_key<'s>(&mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, str>, Error> {
+        self.read.parse_key(scratch)
+    }
+
+    fn parse_value<'s>(&mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, str>, Error> {
+        self.read.parse_value(scratch)
+    }
+
+    fn parse_value_raw<'s>(&mut self, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, [u8]>, Error> {
+        self.read.parse_value_raw(scratch)
+    }
+
+    fn parse_element_type(&mut self) -> Result<ElementType, Error> {
+        self.read.parse_element_type()
+    }
+
+    fn skip_key(&mut self) -> Result<(), Error> {
+        self.read.skip_key()
+    }
+
+    fn skip_value(&mut self) -> Result<(), Error> {
+        self.read.skip_value()
+    }
+
+    fn parse_value_shared(&mut self, scratch: &mut Vec<u8>) -> Result<Bytes, Error> {
+        let value = self.read.parse_value_raw(scratch)?;
+        Ok(self.bytes.slice_ref(&value))
+    }
+
+    fn parse_raw<'s>(&mut self, until: Until, scratch: &'s mut Vec<u8>) -> Result<Reference<'a, 's, [u8]>, Error> {
+        self.read.parse_raw(until, scratch)
+    }
 }
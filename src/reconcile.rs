@@ -0,0 +1,650 @@
+//! Compare the desired configuration with the one loaded in `charon` and plan the commands to converge them.
+//!
+//! The desired objects are the bodies of `load-conn` or `load-pool`, and the live objects are the messages reported by `list-conns`
+//! or `get-pools`, each decoded into a [`Section`] keyed by the name of the object. Typed structures can be turned into sections by
+//! serializing them with [`to_vec`](crate::to_vec) and deserializing the message as a [`Section`].
+//!
+//! `charon` reports many keys that are not part of the desired configuration, such as defaulted values or runtime state. Only keys
+//! present in the desired objects are compared, so such keys never cause a change.
+//!
+//! `charon` also reports some settings differently from how they are loaded, such as `version = IKEv2` for `version = 2`, or the
+//! authentication rounds as `local-1` for a section loaded as `local`. Both sides are normalized to a common representation before
+//! they are compared, so the paths of the changed keys and the ignored paths refer to the normalized keys.
+//!
+//! The `certs`, `cacerts` and `pubkeys` of the authentication rounds are never compared, as they are loaded as the contents of the
+//! certificates and keys but reported as their subjects. A connection is not detected as changed if only its certificates are.
+
+use std::net::IpAddr;
+
+use crate::value::{Section, Value};
+
+/// The kind of objects being compared.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Kind {
+    /// Connections, loaded with `load-conn` and reported by `list-conns`.
+    Conn,
+
+    /// Virtual IP address pools, loaded with `load-pool` and reported by `get-pools`.
+    ///
+    /// `get-pools` reports the state of the pools rather than their configuration, so only the `base` and `size` derived from the
+    /// `addrs` of a pool are compared. Attributes of the pools, and pools whose addresses are not a subnet or a range, are never
+    /// detected as changed.
+    Pool,
+}
+
+/// Compares desired objects with live ones.
+///
+/// # Example
+///
+/// ```
+/// use serde_vici::{
+///     reconcile::{Action, Differ, Kind},
+///     swanctl::Section,
+/// };
+///
+/// let gw: Section = [("version", "2")].into_iter().collect();
+/// let desired: Section = [("gw", gw)].into_iter().collect();
+///
+/// let gw: Section = [("version", "2"), ("unique", "no")].into_iter().collect();
+/// let old: Section = [("version", "1")].into_iter().collect();
+/// let live: Section = [("gw", gw), ("old", old)].into_iter().collect();
+///
+/// let plan = Differ::new(Kind::Conn).diff([&desired], [&live]);
+/// let actions: Vec<_> = plan.entries().iter().map(|entry| (entry.name.as_str(), &entry.action)).collect();
+/// assert_eq!(actions, vec![("gw", &Action::Unchanged), ("old", &Action::Unload)]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Differ {
+    kind: Kind,
+    ignored: Vec<Vec<String>>,
+}
+
+/// The plan to converge the live objects to the desired ones.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Plan {
+    kind: Kind,
+    entries: Vec<Entry>,
+}
+
+/// The action planned for a single object.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    /// The name of the object.
+    pub name: String,
+
+    /// The action to take.
+    pub action: Action,
+}
+
+/// Represents what needs to be done to converge an object.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// The object is not loaded and needs to be loaded with the given message.
+    Load(Section),
+
+    /// The object is loaded but differs, and needs to be loaded again with the given message.
+    Change {
+        /// The paths of the keys that differ, separated by dots.
+        changed: Vec<String>,
+
+        /// The message to load the object with.
+        message: Section,
+    },
+
+    /// The object is loaded as desired.
+    Unchanged,
+
+    /// The object is loaded but not desired, and needs to be unloaded.
+    Unload,
+}
+
+impl Kind {
+    /// Returns the command that loads an object of this kind.
+    pub fn load_command(self) -> &'static str {
+        match self {
+            Kind::Conn => "load-conn",
+            Kind::Pool => "load-pool",
+        }
+    }
+
+    /// Returns the command that unloads an object of this kind.
+    pub fn unload_command(self) -> &'static str {
+        match self {
+            Kind::Conn => "unload-conn",
+            Kind::Pool => "unload-pool",
+        }
+    }
+}
+
+impl Differ {
+    /// Creates a differ for the given kind of objects.
+    pub fn new(kind: Kind) -> Self {
+        let ignored = vec![];
+        Self { kind, ignored }
+    }
+
+    /// Ignores the keys at the given path, separated by dots, relative to each object.
+    ///
+    /// A segment of `*` matches any key, so that `children.*.mode` ignores the mode of every child. Authentication rounds are
+    /// referred to as `local-1`, `remote-1`, and so on.
+    pub fn ignore(mut self, path: &str) -> Self {
+        self.ignored.push(path.split('.').map(str::to_string).collect());
+        self
+    }
+
+    /// Compares the desired objects with the live ones.
+    ///
+    /// Each section of `desired` and `live` contains objects keyed by their names, as in the messages of `load-conn` or `list-conns`.
+    pub fn diff<'a, D, L>(&self, desired: D, live: L) -> Plan
+    where
+        D: IntoIterator<Item = &'a Section>,
+        L: IntoIterator<Item = &'a Section>,
+    {
+        let desired = objects(desired);
+        let live = objects(live);
+
+        let mut entries = vec![];
+        for (name, message) in &desired {
+            let action = match live.iter().find(|(k, _)| k == name) {
+                None => Action::Load(named(name, message)),
+                Some((_, current)) => {
+                    let (normalized, current) = (self.normalize(message, false), self.normalize(current, true));
+                    let mut changed = vec![];
+                    self.compare(&mut vec![], &normalized, &current, &mut changed);
+                    if changed.is_empty() {
+                        Action::Unchanged
+                    } else {
+                        let message = named(name, message);
+                        Action::Change { changed, message }
+                    }
+                },
+            };
+            let name = name.to_string();
+            entries.push(Entry { name, action });
+        }
+
+        for (name, _) in &live {
+            if !desired.iter().any(|(k, _)| k == name) {
+                let name = name.to_string();
+                let action = Action::Unload;
+                entries.push(Entry { name, action });
+            }
+        }
+
+        let kind = self.kind;
+        Plan { kind, entries }
+    }
+
+    fn compare<'a>(&self, path: &mut Vec<&'a str>, desired: &'a Value, live: &Value, changed: &mut Vec<String>) {
+        if self.is_ignored(path) {
+            return;
+        }
+
+        match (desired, live) {
+            (Value::Section(desired), Value::Section(live)) => {
+                for (k, v) in desired.iter() {
+                    path.push(k);
+                    match live.get(k) {
+                        Some(live) => self.compare(path, v, live, changed),
+                        None if !self.is_ignored(path) => changed.push(path.join(".")),
+                        None => {},
+                    }
+                    path.pop();
+                }
+            },
+            (desired, live) if items(desired).is_some_and(|items_desired| Some(items_desired) == items(live)) => {},
+            _ => changed.push(path.join(".")),
+        }
+    }
+
+    /// Rewrites an object into the representation it is compared in.
+    fn normalize(&self, object: &Value, live: bool) -> Value {
+        match (self.kind, object) {
+            (Kind::Conn, Value::Section(conn)) => Value::Section(normalize_conn(conn, live)),
+            (Kind::Pool, Value::Section(pool)) if !live => Value::Section(normalize_pool(pool)),
+            (_, object) => object.clone(),
+        }
+    }
+
+    fn is_ignored(&self, path: &[&str]) -> bool {
+        self.ignored
+            .iter()
+            .any(|pattern| pattern.len() == path.len() && pattern.iter().zip(path).all(|(pattern, key)| pattern == "*" || pattern == key))
+    }
+}
+
+impl Plan {
+    /// Returns the kind of objects this plan converges.
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Returns the actions for each object, the desired objects first in order followed by the objects to unload.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns true if all the objects are loaded as desired.
+    pub fn is_converged(&self) -> bool {
+        self.entries.iter().all(|entry| entry.action == Action::Unchanged)
+    }
+
+    /// Returns the commands and their messages to converge the objects, unloading objects before loading the others.
+    pub fn commands(&self) -> impl Iterator<Item = (&'static str, Section)> + '_ {
+        let unload = self.entries.iter().filter(|entry| entry.action == Action::Unload).map(|entry| {
+            let message = [("name", entry.name.as_str())].into_iter().collect();
+            (self.kind.unload_command(), message)
+        });
+        let load = self.entries.iter().filter_map(|entry| match &entry.action {
+            Action::Load(message) | Action::Change { message, .. } => Some((self.kind.load_command(), message.clone())),
+            Action::Unchanged | Action::Unload => None,
+        });
+        unload.chain(load)
+    }
+}
+
+fn objects<'a>(sections: impl IntoIterator<Item = &'a Section>) -> Vec<(&'a str, &'a Value)> {
+    sections.into_iter().flat_map(Section::iter).collect()
+}
+
+fn named(name: &str, value: &Value) -> Section {
+    [(name, value.clone())].into_iter().collect()
+}
+
+/// Numbers the authentication rounds, and rewrites the settings `list-conns` reports by name into the values `load-conn` accepts.
+fn normalize_conn(conn: &Section, live: bool) -> Section {
+    let (mut local, mut remote) = (0, 0);
+    conn.iter()
+        .map(|(k, v)| match (k, v) {
+            // `load-conn` accepts any section whose name starts with `local` or `remote`, which `list-conns` numbers from 1.
+            (k, Value::Section(auth)) if k.starts_with("local") => {
+                local += 1;
+                (format!("local-{local}"), Value::Section(normalize_auth(auth, live)))
+            },
+            (k, Value::Section(auth)) if k.starts_with("remote") => {
+                remote += 1;
+                (format!("remote-{remote}"), Value::Section(normalize_auth(auth, live)))
+            },
+            ("children", Value::Section(children)) => {
+                let children = children.iter().map(|(name, child)| match child {
+                    Value::Section(child) => (name, Value::Section(normalize_child(child, live))),
+                    child => (name, child.clone()),
+                });
+                (k.to_string(), Value::Section(children.collect()))
+            },
+            ("version", v) if live => {
+                let v = map_str(v, |v| match v {
+                    "IKEv1/2" => "0".to_string(),
+                    "IKEv1" => "1".to_string(),
+                    "IKEv2" => "2".to_string(),
+                    v => v.to_string(),
+                });
+                (k.to_string(), v)
+            },
+            ("unique", v) if live => {
+                let v = map_str(v, |v| v.strip_prefix("UNIQUE_").unwrap_or(v).to_ascii_lowercase());
+                (k.to_string(), v)
+            },
+            (k, v) => (k.to_string(), v.clone()),
+        })
+        .collect()
+}
+
+/// Rewrites the authentication method `load-conn` accepts into the class `list-conns` reports, and drops the certificates and keys
+/// `load-conn` accepts so that they are not compared with the subjects `list-conns` reports.
+fn normalize_auth(auth: &Section, live: bool) -> Section {
+    auth.iter()
+        .filter(|(k, _)| live || !["certs", "cacerts", "pubkeys"].contains(k))
+        .map(|(k, v)| match k {
+            "auth" if !live => {
+                let v = map_str(v, |v| match v {
+                    v if ["pubkey", "rsa", "ecdsa", "ed25519", "ed448", "bliss"]
+                        .iter()
+                        .any(|p| v.starts_with(p)) =>
+                    {
+                        "public key".to_string()
+                    },
+                    "psk" => "pre-shared key".to_string(),
+                    v if v.starts_with("eap") => "EAP".to_string(),
+                    v if v.starts_with("xauth") => "XAuth".to_string(),
+                    v => v.to_string(),
+                });
+                ("class".to_string(), v)
+            },
+            k => (k.to_string(), v.clone()),
+        })
+        .collect()
+}
+
+/// Rewrites the mode `list-conns` reports in upper case, and the traffic selectors it reports as `local-ts` and `remote-ts`.
+fn normalize_child(child: &Section, live: bool) -> Section {
+    child
+        .iter()
+        .map(|(k, v)| match k {
+            "mode" => (k.to_string(), map_str(v, str::to_ascii_lowercase)),
+            "local-ts" if live => ("local_ts".to_string(), v.clone()),
+            "remote-ts" if live => ("remote_ts".to_string(), v.clone()),
+            k => (k.to_string(), v.clone()),
+        })
+        .collect()
+}
+
+/// Derives the `base` and `size` that `get-pools` reports from the `addrs` of a pool, leaving the section empty if they cannot be
+/// derived.
+fn normalize_pool(pool: &Section) -> Section {
+    let Some((base, size)) = pool.get("addrs").and_then(Value::as_str).and_then(pool_range) else {
+        return Section::new();
+    };
+    [("base", base.to_string()), ("size", size.to_string())].into_iter().collect()
+}
+
+/// Returns the first address and the number of addresses of a pool given as a subnet or a range, as `charon` allocates them.
+fn pool_range(addrs: &str) -> Option<(IpAddr, u128)> {
+    // `charon` limits the size of a pool to 31 bits.
+    const POOL_LIMIT: u32 = 31;
+
+    if let Some((from, to)) = addrs.split_once('-') {
+        let from: IpAddr = from.trim().parse().ok()?;
+        let to: IpAddr = to.trim().parse().ok()?;
+        if from.is_ipv4() != to.is_ipv4() {
+            return None;
+        }
+        let size = to_bits(to).checked_sub(to_bits(from))? + 1;
+        return Some((from, size));
+    }
+
+    let (base, prefix) = addrs.split_once('/')?;
+    let base: IpAddr = base.trim().parse().ok()?;
+    let addr_bits = if base.is_ipv4() { 32 } else { 128 };
+    let host_bits = (addr_bits - prefix.trim().parse::<u32>().ok()?.min(addr_bits)).min(POOL_LIMIT);
+
+    // The network address, or the addresses before the base, and the broadcast address are not allocated.
+    let mut size = 1u128 << host_bits;
+    if size > 2 {
+        let offset = to_bits(base) & (size - 1);
+        size -= offset.max(1) + 1;
+    }
+    Some((base, size))
+}
+
+fn to_bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u32::from(addr).into(),
+        IpAddr::V6(addr) => addr.into(),
+    }
+}
+
+/// Rewrites a value that is valid UTF-8 with the given function.
+fn map_str(value: &Value, f: impl Fn(&str) -> String) -> Value {
+    match value.as_str() {
+        Some(v) => Value::from(f(v)),
+        None => value.clone(),
+    }
+}
+
+/// Returns the items of a value, treating a single value the same as a list of one item.
+fn items(value: &Value) -> Option<Vec<&[u8]>> {
+    match value {
+        Value::Bytes(v) => Some(vec![v]),
+        Value::List(v) => Some(v.iter().map(Vec::as_slice).collect()),
+        Value::Section(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use pretty_assertions::assert_eq;
+    use serde_derive::Serialize;
+
+    use super::*;
+    use crate::{de::from_slice, value::to_value};
+
+    /// The message of a `list-conn` event captured from `charon` 5.9.
+    #[rustfmt::skip]
+    const LIST_CONN: &[u8] = &[
+        // gw
+        1, 2, b'g', b'w',
+        // local_addrs
+        4, 11, b'l', b'o', b'c', b'a', b'l', b'_', b'a', b'd', b'd', b'r', b's',
+        // 192.0.2.1
+        5, 0, 9, b'1', b'9', b'2', b'.', b'0', b'.', b'2', b'.', b'1',
+        // local_addrs end
+        6,
+        // remote_addrs
+        4, 12, b'r', b'e', b'm', b'o', b't', b'e', b'_', b'a', b'd', b'd', b'r', b's',
+        // 192.0.2.2
+        5, 0, 9, b'1', b'9', b'2', b'.', b'0', b'.', b'2', b'.', b'2',
+        // remote_addrs end
+        6,
+        // version = IKEv2
+        3, 7, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0, 5, b'I', b'K', b'E', b'v', b'2',
+        // reauth_time = 0
+        3, 11, b'r', b'e', b'a', b'u', b't', b'h', b'_', b't', b'i', b'm', b'e', 0, 1, b'0',
+        // rekey_time = 14400
+        3, 10, b'r', b'e', b'k', b'e', b'y', b'_', b't', b'i', b'm', b'e', 0, 5, b'1', b'4', b'4', b'0', b'0',
+        // unique = UNIQUE_NO
+        3, 6, b'u', b'n', b'i', b'q', b'u', b'e', 0, 9, b'U', b'N', b'I', b'Q', b'U', b'E', b'_', b'N', b'O',
+        // dpd_delay = 30
+        3, 9, b'd', b'p', b'd', b'_', b'd', b'e', b'l', b'a', b'y', 0, 2, b'3', b'0',
+        // local-1
+        1, 7, b'l', b'o', b'c', b'a', b'l', b'-', b'1',
+        // class = public key
+        3, 5, b'c', b'l', b'a', b's', b's', 0, 10, b'p', b'u', b'b', b'l', b'i', b'c', b' ', b'k', b'e', b'y',
+        // id = gw.example.org
+        3, 2, b'i', b'd', 0, 14, b'g', b'w', b'.', b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'o', b'r', b'g',
+        // certs
+        4, 5, b'c', b'e', b'r', b't', b's',
+        // CN=gw.example.org
+        5, 0, 17, b'C', b'N', b'=', b'g', b'w', b'.', b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'o', b'r', b'g',
+        // certs end
+        6,
+        // local-1 end
+        2,
+        // remote-1
+        1, 8, b'r', b'e', b'm', b'o', b't', b'e', b'-', b'1',
+        // class = public key
+        3, 5, b'c', b'l', b'a', b's', b's', 0, 10, b'p', b'u', b'b', b'l', b'i', b'c', b' ', b'k', b'e', b'y',
+        // id = peer.example.org
+        3, 2, b'i', b'd', 0, 16, b'p', b'e', b'e', b'r', b'.', b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'o', b'r', b'g',
+        // remote-1 end
+        2,
+        // children
+        1, 8, b'c', b'h', b'i', b'l', b'd', b'r', b'e', b'n',
+        // net
+        1, 3, b'n', b'e', b't',
+        // mode = TUNNEL
+        3, 4, b'm', b'o', b'd', b'e', 0, 6, b'T', b'U', b'N', b'N', b'E', b'L',
+        // rekey_time = 3600
+        3, 10, b'r', b'e', b'k', b'e', b'y', b'_', b't', b'i', b'm', b'e', 0, 4, b'3', b'6', b'0', b'0',
+        // rekey_bytes = 0
+        3, 11, b'r', b'e', b'k', b'e', b'y', b'_', b'b', b'y', b't', b'e', b's', 0, 1, b'0',
+        // rekey_packets = 0
+        3, 13, b'r', b'e', b'k', b'e', b'y', b'_', b'p', b'a', b'c', b'k', b'e', b't', b's', 0, 1, b'0',
+        // dpd_action = clear
+        3, 10, b'd', b'p', b'd', b'_', b'a', b'c', b't', b'i', b'o', b'n', 0, 5, b'c', b'l', b'e', b'a', b'r',
+        // close_action = none
+        3, 12, b'c', b'l', b'o', b's', b'e', b'_', b'a', b'c', b't', b'i', b'o', b'n', 0, 4, b'n', b'o', b'n', b'e',
+        // local-ts
+        4, 8, b'l', b'o', b'c', b'a', b'l', b'-', b't', b's',
+        // 10.1.0.0/16
+        5, 0, 11, b'1', b'0', b'.', b'1', b'.', b'0', b'.', b'0', b'/', b'1', b'6',
+        // local-ts end
+        6,
+        // remote-ts
+        4, 9, b'r', b'e', b'm', b'o', b't', b'e', b'-', b't', b's',
+        // 10.2.0.0/16
+        5, 0, 11, b'1', b'0', b'.', b'2', b'.', b'0', b'.', b'0', b'/', b'1', b'6',
+        // remote-ts end
+        6,
+        // net end
+        2,
+        // children end
+        2,
+        // gw end
+        2,
+    ];
+
+    #[derive(Serialize)]
+    struct Conn<'a> {
+        local_addrs: Vec<&'a str>,
+        remote_addrs: Vec<&'a str>,
+        version: u32,
+        unique: &'a str,
+        local: Auth<'a>,
+        remote: Auth<'a>,
+        children: BTreeMap<&'a str, Child<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct Auth<'a> {
+        auth: &'a str,
+        id: &'a str,
+        certs: Vec<&'a str>,
+    }
+
+    #[derive(Serialize)]
+    struct Child<'a> {
+        mode: &'a str,
+        local_ts: Vec<&'a str>,
+        remote_ts: Vec<&'a str>,
+    }
+
+    fn conn(name: &str, remote: &str, mode: &str) -> Section {
+        let conn = Conn {
+            local_addrs: vec!["192.0.2.1"],
+            remote_addrs: vec![remote],
+            version: 2,
+            unique: "no",
+            local: Auth {
+                auth: "pubkey",
+                id: "gw.example.org",
+                certs: vec!["-----BEGIN CERTIFICATE-----\nMIIB...\n-----END CERTIFICATE-----\n"],
+            },
+            remote: Auth {
+                auth: "pubkey",
+                id: "peer.example.org",
+                certs: vec![],
+            },
+            children: BTreeMap::from([(
+                "net",
+                Child {
+                    mode,
+                    local_ts: vec!["10.1.0.0/16"],
+                    remote_ts: vec!["10.2.0.0/16"],
+                },
+            )]),
+        };
+        let Value::Section(section) = to_value(&BTreeMap::from([(name, conn)])).unwrap() else {
+            unreachable!();
+        };
+        section
+    }
+
+    #[test]
+    fn diff_conns() {
+        let desired = [conn("gw", "192.0.2.2", "tunnel"), conn("gw-02", "192.0.2.3", "tunnel")];
+        let current: [Section; 2] = [from_slice(LIST_CONN).unwrap(), [("gw-03", Section::new())].into_iter().collect()];
+
+        let plan = Differ::new(Kind::Conn).diff(&desired, &current);
+        assert_eq!(
+            plan.entries(),
+            &[
+                Entry {
+                    name: "gw".to_string(),
+                    action: Action::Unchanged,
+                },
+                Entry {
+                    name: "gw-02".to_string(),
+                    action: Action::Load(desired[1].clone()),
+                },
+                Entry {
+                    name: "gw-03".to_string(),
+                    action: Action::Unload,
+                },
+            ]
+        );
+        assert!(!plan.is_converged());
+        assert_eq!(
+            plan.commands()
+                .map(|(command, message)| (command, message.keys().next().unwrap().to_string()))
+                .collect::<Vec<_>>(),
+            vec![("unload-conn", "name".to_string()), ("load-conn", "gw-02".to_string())]
+        );
+
+        let desired = conn("gw", "192.0.2.20", "transport");
+        let plan = Differ::new(Kind::Conn).diff([&desired], &current[..1]);
+        assert_eq!(
+            plan.entries()[0].action,
+            Action::Change {
+                changed: vec!["remote_addrs".to_string(), "children.net.mode".to_string()],
+                message: desired.clone(),
+            }
+        );
+
+        let plan = Differ::new(Kind::Conn)
+            .ignore("remote_addrs")
+            .ignore("children.*.mode")
+            .diff([&desired], &current[..1]);
+        assert!(plan.is_converged());
+    }
+
+    #[test]
+    fn diff_pools() {
+        let desired: Section = [
+            (
+                "rw",
+                Value::Section([("addrs", "10.0.0.0/24"), ("dns", "10.0.0.53")].into_iter().collect()),
+            ),
+            ("guest", Value::Section([("addrs", "10.0.1.0/24")].into_iter().collect())),
+            ("dhcp", Value::Section([("addrs", "dhcp")].into_iter().collect())),
+        ]
+        .into_iter()
+        .collect();
+        let current: Section = [
+            ("rw", Value::Section([("base", "10.0.0.0"), ("size", "254")].into_iter().collect())),
+            ("dhcp", Value::Section([("base", "0.0.0.0"), ("size", "0")].into_iter().collect())),
+        ]
+        .into_iter()
+        .collect();
+
+        let plan = Differ::new(Kind::Pool).diff([&desired], [&current]);
+        assert_eq!(
+            plan.entries().iter().map(|entry| &entry.action).collect::<Vec<_>>(),
+            vec![
+                &Action::Unchanged,
+                &Action::Load(
+                    [("guest", Value::Section([("addrs", "10.0.1.0/24")].into_iter().collect()))]
+                        .into_iter()
+                        .collect()
+                ),
+                &Action::Unchanged,
+            ]
+        );
+
+        let desired: Section = [("rw", Value::Section([("addrs", "10.0.0.0/25")].into_iter().collect()))]
+            .into_iter()
+            .collect();
+        let plan = Differ::new(Kind::Pool).diff([&desired], [&current]);
+        assert_eq!(
+            plan.entries()[0].action,
+            Action::Change {
+                changed: vec!["size".to_string()],
+                message: desired.clone(),
+            }
+        );
+    }
+
+    #[test]
+    fn pool_ranges() {
+        let range = |base: &str, size| Some((base.parse().unwrap(), size));
+        assert_eq!(pool_range("10.0.0.0/24"), range("10.0.0.0", 254));
+        assert_eq!(pool_range("10.0.0.5/24"), range("10.0.0.5", 250));
+        assert_eq!(pool_range("10.0.0.1/32"), range("10.0.0.1", 1));
+        assert_eq!(pool_range("10.0.0.1-10.0.0.10"), range("10.0.0.1", 10));
+        assert_eq!(pool_range("fd00::/64"), range("fd00::", (1 << 31) - 2));
+        assert_eq!(pool_range("10.0.0.10-10.0.0.1"), None);
+        assert_eq!(pool_range("dhcp"), None);
+    }
+}
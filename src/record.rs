@@ -0,0 +1,528 @@
+//! Record VICI packets exchanged on a transport for later inspection or replay.
+//!
+//! A recording is itself a VICI message: every frame is a section named by its zero-based index, which makes it possible to append
+//! frames as they occur and to read the recording back with [`from_reader`](crate::from_reader) or [`from_slice`](crate::from_slice).
+//!
+//! ```text
+//! 0 {
+//!     direction = sent
+//!     timestamp = 1650000000000000
+//!     type = CMD_REQUEST
+//!     name = version
+//!     body [
+//!         <binary>
+//!     ]
+//! }
+//! 1 {
+//!     ...
+//! }
+//! ```
+
+use std::{
+    fmt, io,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{
+    de,
+    ser::{self, SerializeMap},
+    Deserialize, Serialize,
+};
+
+use crate::{
+    error::{Error, Result},
+    packet::{decode, Packet, PacketType},
+    ser::to_writer,
+};
+
+/// The direction of a recorded frame, as seen from the side of the recorded transport.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// The frame was written to the transport.
+    Sent,
+
+    /// The frame was read from the transport.
+    Received,
+}
+
+/// A structure representing a single recorded packet.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Frame {
+    /// The direction of the packet.
+    pub direction: Direction,
+
+    /// The time at which the packet was completely sent or received.
+    #[serde(with = "timestamp")]
+    pub timestamp: SystemTime,
+
+    /// The type of the packet.
+    #[serde(rename = "type")]
+    pub packet_type: PacketType,
+
+    /// The name of the command or event, present only for named packet types.
+    pub name: Option<String>,
+
+    /// The message of the packet encoded as VICI.
+    ///
+    /// The body is recorded as a list of chunks, as a packet may exceed the maximum length of a single value.
+    #[serde(default, with = "body")]
+    pub body: Vec<u8>,
+}
+
+/// A structure representing all the frames of a recording in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Recording {
+    /// The recorded frames.
+    pub frames: Vec<Frame>,
+}
+
+/// A transport wrapper that records every packet written to or read from the inner transport.
+///
+/// Packets are recorded once they are complete, so the inner transport may be read or written in chunks of any size.
+///
+/// Failing to record a packet never fails the read or write on the inner transport: the failure is logged, recording is disabled,
+/// and the error is kept to be inspected with [`Recorder::error`].
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use serde_vici::{
+///     packet::{write_packet, Packet},
+///     record::{Direction, Recorder, Recording},
+/// };
+///
+/// fn main() -> Result<()> {
+///     let mut recording = Vec::new();
+///     let mut recorder = Recorder::new(Vec::new(), &mut recording);
+///     write_packet(&mut recorder, &Packet::request("version", &())?)?;
+///
+///     let recording: Recording = serde_vici::from_slice(&recording)?;
+///     assert_eq!(recording.frames[0].direction, Direction::Sent);
+///     assert_eq!(recording.frames[0].name.as_deref(), Some("version"));
+///     Ok(())
+/// }
+/// ```
+pub struct Recorder<S, W> {
+    inner: S,
+    sink: W,
+    index: usize,
+    sent: Vec<u8>,
+    received: Vec<u8>,
+    error: Option<Error>,
+}
+
+impl Frame {
+    /// Creates a frame for the given packet.
+    pub fn new(direction: Direction, timestamp: SystemTime, packet: Packet) -> Self {
+        Self {
+            direction,
+            timestamp,
+            packet_type: packet.packet_type,
+            name: packet.name,
+            body: packet.body,
+        }
+    }
+
+    /// Returns the recorded packet.
+    pub fn packet(&self) -> Packet {
+        Packet::new(self.packet_type, self.name.clone(), self.body.clone())
+    }
+}
+
+impl<S, W> Recorder<S, W>
+where
+    W: io::Write,
+{
+    /// Creates a new recorder that writes the frames into `sink`.
+    pub fn new(inner: S, sink: W) -> Self {
+        let index = 0;
+        let sent = vec![];
+        let received = vec![];
+        let error = None;
+        Self {
+            inner,
+            sink,
+            index,
+            sent,
+            received,
+            error,
+        }
+    }
+
+    /// Gets a reference to the inner transport.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the inner transport.
+    ///
+    /// Packets read from or written to the inner transport directly are not recorded.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns the error that disabled recording, if any.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// Unwraps this recorder, returning the inner transport and the sink.
+    pub fn into_inner(self) -> (S, W) {
+        (self.inner, self.sink)
+    }
+
+    fn append(&mut self, direction: Direction, data: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+
+        match direction {
+            Direction::Sent => self.sent.extend_from_slice(data),
+            Direction::Received => self.received.extend_from_slice(data),
+        }
+        if let Err(e) = self.record(direction) {
+            log::warn!("recording disabled: {e}");
+            self.sent = vec![];
+            self.received = vec![];
+            self.error = Some(e);
+        }
+    }
+
+    fn record(&mut self, direction: Direction) -> Result<()> {
+        loop {
+            let buf = match direction {
+                Direction::Sent => &mut self.sent,
+                Direction::Received => &mut self.received,
+            };
+
+            let Some(len) = buf.get(..4).map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize) else {
+                return Ok(());
+            };
+            if buf.len() < 4 + len {
+                return Ok(());
+            }
+
+            let packet = decode(&buf[4..4 + len])?;
+            buf.drain(..4 + len);
+
+            let frame = Frame::new(direction, SystemTime::now(), packet);
+            to_writer(&mut self.sink, &Indexed(self.index, &frame))?;
+            self.sink.flush()?;
+            self.index += 1;
+        }
+    }
+}
+
+impl<S, W> io::Read for Recorder<S, W>
+where
+    S: io::Read,
+    W: io::Write,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.append(Direction::Received, &buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<S, W> io::Write for Recorder<S, W>
+where
+    S: io::Write,
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.append(Direction::Sent, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct Indexed<'a>(usize, &'a Frame);
+
+impl Serialize for Indexed<'_> {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(&self.0, self.1)?;
+        map.end()
+    }
+}
+
+impl Serialize for Recording {
+    fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_map(self.frames.iter().enumerate())
+    }
+}
+
+impl<'de> Deserialize<'de> for Recording {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RecordingVisitor;
+
+        impl<'de> de::Visitor<'de> for RecordingVisitor {
+            type Value = Recording;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a recording of VICI frames")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> core::result::Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut frames = vec![];
+                while let Some((_, frame)) = map.next_entry::<de::IgnoredAny, Frame>()? {
+                    frames.push(frame);
+                }
+                Ok(Recording { frames })
+            }
+        }
+
+        deserializer.deserialize_map(RecordingVisitor)
+    }
+}
+
+mod timestamp {
+    use super::*;
+
+    pub fn serialize<S>(timestamp: &SystemTime, serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let micros = timestamp.duration_since(UNIX_EPOCH).map_err(ser::Error::custom)?.as_micros();
+        let micros = u64::try_from(micros).map_err(ser::Error::custom)?;
+        serializer.serialize_u64(micros)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> core::result::Result<SystemTime, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let micros = u64::deserialize(deserializer)?;
+        Ok(UNIX_EPOCH + Duration::from_micros(micros))
+    }
+}
+
+mod body {
+    use super::*;
+
+    /// The maximum length of a single VICI value.
+    const CHUNK_SIZE: usize = u16::MAX as usize;
+
+    struct Chunk<'a>(&'a [u8]);
+
+    impl Serialize for Chunk<'_> {
+        fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+        {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    /// Serializes the body as a list of chunks, as a packet can be larger than a single value can hold.
+    pub fn serialize<S>(body: &[u8], serializer: S) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.collect_seq(body.chunks(CHUNK_SIZE).map(Chunk))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> core::result::Result<Vec<u8>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct BodyVisitor;
+
+        impl<'de> de::Visitor<'de> for BodyVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a VICI message")
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(v)
+            }
+
+            fn visit_str<E>(self, v: &str) -> core::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(v.as_bytes().to_vec())
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut body = vec![];
+                while let Some(ChunkBuf(chunk)) = seq.next_element()? {
+                    body.extend_from_slice(&chunk);
+                }
+                Ok(body)
+            }
+        }
+
+        struct ChunkBuf(Vec<u8>);
+
+        impl<'de> Deserialize<'de> for ChunkBuf {
+            fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                deserializer.deserialize_byte_buf(BodyVisitor).map(ChunkBuf)
+            }
+        }
+
+        deserializer.deserialize_any(BodyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{
+        de::from_slice,
+        packet::{read_packet, write_packet},
+    };
+
+    struct Duplex {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl io::Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl io::Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_both_directions() {
+        let mut response = vec![];
+        write_packet(&mut response, &Packet::response(&()).unwrap()).unwrap();
+        write_packet(&mut response, &Packet::event("log", &()).unwrap()).unwrap();
+
+        let mut recording = vec![];
+        let transport = Duplex {
+            input: io::Cursor::new(response),
+            output: vec![],
+        };
+        let mut recorder = Recorder::new(transport, &mut recording);
+
+        // The packet is written in chunks to exercise the buffering.
+        let request = crate::packet::encode(&Packet::request("version", &()).unwrap()).unwrap();
+        for chunk in request.chunks(3) {
+            recorder.write_all(chunk).unwrap();
+        }
+        assert_eq!(read_packet(&mut recorder).unwrap(), Packet::response(&()).unwrap());
+        assert_eq!(read_packet(&mut recorder).unwrap(), Packet::event("log", &()).unwrap());
+        assert_eq!(recorder.get_ref().output, request);
+
+        let actual: Recording = from_slice(&recording).unwrap();
+        let actual: Vec<_> = actual.frames.iter().map(|frame| (frame.direction, frame.packet())).collect();
+        assert_eq!(
+            actual,
+            vec![
+                (Direction::Sent, Packet::request("version", &()).unwrap()),
+                (Direction::Received, Packet::response(&()).unwrap()),
+                (Direction::Received, Packet::event("log", &()).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let recording = Recording {
+            frames: vec![Frame::new(
+                Direction::Sent,
+                UNIX_EPOCH + Duration::from_micros(1_650_000_000_000_000),
+                Packet::request(
+                    "list-conns",
+                    &[("conn", "gw")].into_iter().collect::<std::collections::BTreeMap<_, _>>(),
+                )
+                .unwrap(),
+            )],
+        };
+
+        let actual: Recording = from_slice(&crate::ser::to_vec(&recording).unwrap()).unwrap();
+        assert_eq!(actual, recording);
+    }
+
+    #[test]
+    fn round_trip_large_body() {
+        let recording = Recording {
+            frames: vec![Frame {
+                direction: Direction::Received,
+                timestamp: UNIX_EPOCH,
+                packet_type: PacketType::CmdResponse,
+                name: None,
+                body: (0..200_000).map(|i| i as u8).collect(),
+            }],
+        };
+
+        let actual: Recording = from_slice(&crate::ser::to_vec(&recording).unwrap()).unwrap();
+        assert_eq!(actual, recording);
+    }
+
+    #[test]
+    fn record_failure_disables_recording() {
+        // A malformed packet cannot be decoded, but is still passed through to the transport.
+        #[rustfmt::skip]
+        let malformed = [
+            // length
+            0, 0, 0, 1,
+            // type
+            0xff,
+        ];
+        let request = crate::packet::encode(&Packet::request("version", &()).unwrap()).unwrap();
+
+        let mut recording = vec![];
+        let mut recorder = Recorder::new(vec![], &mut recording);
+        recorder.write_all(&malformed).unwrap();
+        recorder.write_all(&request).unwrap();
+        assert!(recorder.error().unwrap().is_data());
+        assert_eq!(recorder.get_ref(), &[&malformed[..], &request].concat());
+
+        let (_, recording) = recorder.into_inner();
+        assert!(recording.is_empty());
+    }
+}
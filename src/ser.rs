@@ -1,13 +1,14 @@
 //! Serialize a Rust data structure using the VICI protocol.
 
-use std::{io, str};
+use std::{borrow::Cow, io, str};
 
-use bytes::BufMut;
+use bytes::{BufMut, Bytes, BytesMut};
 use serde::{ser, Serialize};
 
 use crate::{
     error::{Error, Result},
-    value::{FieldType, ListElement},
+    field::{FieldType, ListElement},
+    raw::{RAW_LIST_TOKEN, RAW_SECTION_TOKEN},
     ElementType,
 };
 
@@ -17,6 +18,7 @@ pub(crate) enum State {
     Key(FieldType),
     Value,
     ListItem(ListElement, Option<usize>),
+    Raw,
 }
 
 /// A structure for serializing Rust values using the VICI protocol.
@@ -51,6 +53,82 @@ pub struct Serializer<'a, W> {
     writer: &'a mut W,
     level: Option<usize>,
     state: State,
+    options: SerializerOptions,
+}
+
+/// Options changing how the [`Serializer`] encodes values.
+///
+/// The defaults match what `charon` expects and what [`to_vec`] writes.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use serde::Serialize;
+/// use serde_vici::ser::{Emit, FloatFormat, SerializerOptions};
+///
+/// #[derive(Serialize)]
+/// struct Settings {
+///     enabled: bool,
+///     ratio: f64,
+///     comment: Option<String>,
+/// }
+///
+/// fn main() -> Result<()> {
+///     let options = SerializerOptions::new()
+///         .with_bools("1", "0")
+///         .with_none(Emit::Empty)
+///         .with_floats(FloatFormat::Fixed(2));
+///
+///     let settings = Settings {
+///         enabled: true,
+///         ratio: 0.5,
+///         comment: None,
+///     };
+///
+///     #[rustfmt::skip]
+///     let expected = vec![
+///         // enabled = 1
+///         3, 7, b'e', b'n', b'a', b'b', b'l', b'e', b'd', 0, 1, b'1',
+///         // ratio = 0.50
+///         3, 5, b'r', b'a', b't', b'i', b'o', 0, 4, b'0', b'.', b'5', b'0',
+///         // comment =
+///         3, 7, b'c', b'o', b'm', b'm', b'e', b'n', b't', 0, 0,
+///     ];
+///     assert_eq!(serde_vici::to_vec_with(&settings, &options)?, expected);
+///     assert_eq!(serde_vici::serialized_size_with(&settings, &options)?, expected.len());
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SerializerOptions {
+    bools: (Cow<'static, str>, Cow<'static, str>),
+    none: Emit,
+    empty_lists: Emit,
+    floats: FloatFormat,
+}
+
+/// How a value without contents is encoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Emit {
+    /// The key and the value are left out.
+    Skip,
+
+    /// The key is written with an empty value, or an empty list.
+    Empty,
+}
+
+/// How floating-point numbers are formatted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FloatFormat {
+    /// The shortest representation that parses back to the same number, in scientific notation for very large or small numbers.
+    Shortest,
+
+    /// The shortest representation that parses back to the same number, never in scientific notation.
+    Plain,
+
+    /// A fixed number of decimal places.
+    Fixed(usize),
 }
 
 /// Serialize the given data structure as a VICI byte vector.
@@ -67,6 +145,20 @@ where
     Ok(buf)
 }
 
+/// Serialize the given data structure as a VICI byte vector using the given options.
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+pub fn to_vec_with<T>(value: &T, options: &SerializerOptions) -> Result<Vec<u8>>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut buf = vec![];
+    let mut serializer = Serializer::new(&mut buf).with_options(options.clone());
+    value.serialize(&mut serializer)?;
+    Ok(buf)
+}
+
 /// Serialize the given data structure as VICI into the IO stream.
 ///
 /// # Errors
@@ -81,6 +173,107 @@ where
     Ok(())
 }
 
+/// Serialize the given data structure as a frame of VICI into the asynchronous IO stream.
+///
+/// The data structure is serialized into memory first and then written to the stream as a whole, prefixed with its length as a 32-bit
+/// big-endian integer, which [`from_async_reader`](crate::from_async_reader) expects. The stream is flushed afterwards. Any stream
+/// implementing `futures_io::AsyncWrite` can be used regardless of the runtime driving it.
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error, if the frame exceeds
+/// [`MAX_PACKET_SIZE`](crate::packet::MAX_PACKET_SIZE), or if writing to the stream fails.
+#[cfg(feature = "futures-io")]
+pub async fn to_async_writer<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: ?Sized + futures_io::AsyncWrite + Unpin,
+    T: ?Sized + ser::Serialize,
+{
+    let mut buf = vec![0; 4];
+    to_writer(&mut buf, value)?;
+
+    let len = buf.len() - 4;
+    crate::packet::check_packet_size(len, crate::packet::MAX_PACKET_SIZE)?;
+    buf[..4].copy_from_slice(&(len as u32).to_be_bytes());
+    crate::async_io::write_all(writer, &buf).await?;
+    Ok(())
+}
+
+/// Serialize the given data structure as VICI into the buffer.
+///
+/// The buffer can be reused across messages, for example a `BytesMut` that is split into frames, so that serializing does not
+/// allocate once the buffer has grown large enough.
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error, or if the buffer has no more room.
+pub fn to_buf<B, T>(buf: &mut B, value: &T) -> Result<()>
+where
+    B: ?Sized + BufMut,
+    T: ?Sized + ser::Serialize,
+{
+    to_writer(&mut buf.writer(), value)
+}
+
+/// Serialize the given data structure as VICI bytes.
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+pub fn to_bytes<T>(value: &T) -> Result<Bytes>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut buf = BytesMut::new();
+    to_buf(&mut buf, value)?;
+    Ok(buf.freeze())
+}
+
+/// Compute the number of bytes the given data structure is serialized into as VICI, without writing anything.
+///
+/// The size is computed by the same rules as the [`Serializer`], so it always equals the length of [`to_vec`].
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+pub fn serialized_size<T>(value: &T) -> Result<usize>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut counter = Counter { size: 0 };
+    to_writer(&mut counter, value)?;
+    Ok(counter.size)
+}
+
+/// Compute the number of bytes the given data structure is serialized into as VICI using the given options, without writing anything.
+///
+/// The size always equals the length of [`to_vec_with`] with the same options.
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+pub fn serialized_size_with<T>(value: &T, options: &SerializerOptions) -> Result<usize>
+where
+    T: ?Sized + ser::Serialize,
+{
+    let mut counter = Counter { size: 0 };
+    let mut serializer = Serializer::new(&mut counter).with_options(options.clone());
+    value.serialize(&mut serializer)?;
+    Ok(counter.size)
+}
+
+struct Counter {
+    size: usize,
+}
+
+impl io::Write for Counter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.size += buf.len();
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 impl<'a, W> Serializer<'a, W>
 where
     W: io::Write,
@@ -89,7 +282,77 @@ where
     pub fn new(writer: &'a mut W) -> Self {
         let level = None;
         let state = State::None;
-        Self { writer, level, state }
+        let options = SerializerOptions::default();
+        Self {
+            writer,
+            level,
+            state,
+            options,
+        }
+    }
+
+    /// Sets the options changing how values are encoded.
+    pub fn with_options(mut self, options: SerializerOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl SerializerOptions {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self {
+            bools: (Cow::Borrowed("yes"), Cow::Borrowed("no")),
+            none: Emit::Skip,
+            empty_lists: Emit::Empty,
+            floats: FloatFormat::Shortest,
+        }
+    }
+
+    /// Sets how `true` and `false` are spelled, `yes` and `no` by default.
+    pub fn with_bools<T, F>(mut self, true_value: T, false_value: F) -> Self
+    where
+        T: Into<Cow<'static, str>>,
+        F: Into<Cow<'static, str>>,
+    {
+        self.bools = (true_value.into(), false_value.into());
+        self
+    }
+
+    /// Sets how `None` and unit values are encoded, left out by default.
+    ///
+    /// A `None` item of a list is always written as an empty item.
+    pub fn with_none(mut self, none: Emit) -> Self {
+        self.none = none;
+        self
+    }
+
+    /// Sets how empty lists are encoded, written as lists without items by default.
+    pub fn with_empty_lists(mut self, empty_lists: Emit) -> Self {
+        self.empty_lists = empty_lists;
+        self
+    }
+
+    /// Sets how floating-point numbers are formatted, in their shortest representation by default.
+    pub fn with_floats(mut self, floats: FloatFormat) -> Self {
+        self.floats = floats;
+        self
+    }
+}
+
+impl SerializerOptions {
+    pub(crate) fn none(&self) -> Emit {
+        self.none
+    }
+
+    pub(crate) fn empty_lists(&self) -> Emit {
+        self.empty_lists
+    }
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -108,9 +371,15 @@ macro_rules! serialize_float {
     ($method:ident => $type:ident) => {
         #[inline]
         fn $method(self, v: $type) -> Result<Self::Ok> {
-            let mut buf = ryu::Buffer::new();
-            let s = buf.format_finite(v);
-            self.serialize_bytes(s.as_bytes())
+            match self.options.floats {
+                FloatFormat::Shortest => {
+                    let mut buf = ryu::Buffer::new();
+                    let s = buf.format_finite(v);
+                    self.serialize_bytes(s.as_bytes())
+                },
+                FloatFormat::Plain => self.serialize_bytes(v.to_string().as_bytes()),
+                FloatFormat::Fixed(precision) => self.serialize_bytes(format!("{v:.precision$}").as_bytes()),
+            }
         }
     };
 }
@@ -132,8 +401,9 @@ where
 
     #[inline]
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
-        let s = if v { b"yes" as &[u8] } else { b"no" as &[u8] };
-        self.serialize_bytes(s)
+        let (true_value, false_value) = &self.options.bools;
+        let s = if v { true_value.clone() } else { false_value.clone() };
+        self.serialize_bytes(s.as_bytes())
     }
 
     serialize_integer!(serialize_i8 => i8);
@@ -160,18 +430,16 @@ where
 
     #[inline]
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
-        let mut buf = vec![];
         match self.state {
             State::None => {
                 return Err(io::Error::from(io::ErrorKind::InvalidData).into());
             },
+            State::Raw => {},
             State::Key(_) | State::ListItem(_, None) => {
-                buf.put_u8(v.len() as u8);
-                self.writer.write_all(&buf)?;
+                self.writer.write_all(&[v.len() as u8])?;
             },
             State::Value | State::ListItem(_, _) => {
-                buf.put_u16(v.len() as u16);
-                self.writer.write_all(&buf)?;
+                self.writer.write_all(&(v.len() as u16).to_be_bytes())?;
             },
         }
 
@@ -182,7 +450,7 @@ where
     #[inline]
     fn serialize_none(self) -> Result<Self::Ok> {
         match self.state {
-            State::None => {
+            State::None | State::Raw => {
                 return Err(io::Error::from(io::ErrorKind::InvalidData).into());
             },
             State::Key(_) | State::ListItem(_, None) => {
@@ -206,12 +474,16 @@ where
 
     #[inline]
     fn serialize_unit(self) -> Result<Self::Ok> {
-        Ok(())
+        match self.state {
+            State::None | State::Raw => Ok(()),
+            _ if self.options.none == Emit::Empty => self.serialize_none(),
+            _ => Ok(()),
+        }
     }
 
     #[inline]
     fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok> {
-        Ok(())
+        self.serialize_unit()
     }
 
     #[inline]
@@ -220,11 +492,25 @@ where
     }
 
     #[inline]
-    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<Self::Ok>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + serde::Serialize,
     {
-        value.serialize(self)
+        // Raw elements are written verbatim followed by the end of the section or list they were captured from.
+        let end = match name {
+            RAW_SECTION_TOKEN if self.state == State::None && self.level.is_none() => None,
+            RAW_SECTION_TOKEN => Some(ElementType::SectionEnd),
+            RAW_LIST_TOKEN if matches!(self.state, State::ListItem(ListElement::String, _)) => Some(ElementType::ListEnd),
+            RAW_LIST_TOKEN => return Err(io::Error::from(io::ErrorKind::InvalidData).into()),
+            _ => return value.serialize(self),
+        };
+
+        self.state = State::Raw;
+        value.serialize(&mut *self)?;
+        if let Some(end) = end {
+            self.writer.write_all(&[end as u8])?;
+        }
+        Ok(())
     }
 
     #[inline]
@@ -285,18 +571,15 @@ where
     where
         T: ?Sized + serde::Serialize,
     {
-        let mut buf = vec![];
         match self.state {
             State::ListItem(ListElement::String, o) => {
-                buf.put_u8(ElementType::ListItem as u8);
-                self.writer.write_all(&buf)?;
+                self.writer.write_all(&[ElementType::ListItem as u8])?;
 
                 self.state = State::ListItem(ListElement::String, Some(o.map_or(0, |n| n + 1)));
                 value.serialize(&mut **self)?;
             },
             State::ListItem(ListElement::Section, o) => {
-                buf.put_u8(ElementType::SectionStart as u8);
-                self.writer.write_all(&buf)?;
+                self.writer.write_all(&[ElementType::SectionStart as u8])?;
 
                 self.state = State::Key(FieldType::Section);
                 let index = o.unwrap_or_default();
@@ -315,17 +598,15 @@ where
 
     #[inline]
     fn end(self) -> Result<Self::Ok> {
-        let mut buf = vec![];
         match &self.state {
             State::ListItem(ListElement::String, _) => {
-                buf.put_u8(ElementType::ListEnd as u8);
+                self.writer.write_all(&[ElementType::ListEnd as u8])?;
             },
             State::ListItem(ListElement::Section, _) => {
-                buf.put_u8(ElementType::SectionEnd as u8);
+                self.writer.write_all(&[ElementType::SectionEnd as u8])?;
             },
             _ => {},
         }
-        self.writer.write_all(&buf)?;
         Ok(())
     }
 }
@@ -428,9 +709,7 @@ where
             FieldType::String => ElementType::KeyValue,
         };
 
-        let mut buf = vec![];
-        buf.put_u8(element as u8);
-        self.writer.write_all(&buf)?;
+        self.writer.write_all(&[element as u8])?;
 
         key.serialize(&mut **self)
     }
@@ -449,7 +728,7 @@ where
         K: ?Sized + serde::Serialize,
         V: ?Sized + serde::Serialize,
     {
-        self.state = State::Key(FieldType::from(value)?);
+        self.state = State::Key(FieldType::from(value, &self.options)?);
         self.serialize_key(key)?;
 
         match self.state {
@@ -471,9 +750,7 @@ where
             _ => return Ok(()),
         };
 
-        let mut buf = vec![];
-        buf.put_u8(ElementType::SectionEnd as u8);
-        self.writer.write_all(&buf)?;
+        self.writer.write_all(&[ElementType::SectionEnd as u8])?;
 
         Ok(())
     }
@@ -820,4 +1097,176 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn serialize_buf() {
+        let mut buf = BytesMut::with_capacity(64);
+        to_buf(&mut buf, &indexmap! { "key1" => "value1" }).unwrap();
+        let first = buf.split().freeze();
+        to_buf(&mut buf, &indexmap! { "key2" => vec!["item1"] }).unwrap();
+        let second = buf.split().freeze();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            &first[..],
+            &[
+                // key1 = value1
+                3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+            ]
+        );
+
+        #[rustfmt::skip]
+        assert_eq!(
+            &second[..],
+            &[
+                // key2
+                4, 4, b'k', b'e', b'y', b'2',
+                // item1
+                5, 0, 5, b'i', b't', b'e', b'm', b'1',
+                // key2 end
+                6,
+            ]
+        );
+        assert_eq!(to_bytes(&indexmap! { "key1" => "value1" }).unwrap(), first);
+
+        let mut buf = [0; 8];
+        let err = to_buf(&mut &mut buf[..], &indexmap! { "key1" => "value1" }).unwrap_err();
+        assert!(err.is_io());
+    }
+
+    #[cfg(feature = "futures-io")]
+    #[test]
+    fn serialize_async_writer() {
+        use futures::{executor::block_on, io::Cursor};
+
+        let mut writer = Cursor::new(vec![]);
+        block_on(to_async_writer(&mut writer, &indexmap! { "key1" => "value1" })).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            writer.into_inner(),
+            vec![
+                // length
+                0, 0, 0, 14,
+                // key1 = value1
+                3, 4, b'k', b'e', b'y', b'1', 0, 6, b'v', b'a', b'l', b'u', b'e', b'1',
+            ]
+        );
+
+        let mut buf = [0; 8];
+        let mut writer = Cursor::new(&mut buf[..]);
+        let err = block_on(to_async_writer(&mut writer, &indexmap! { "key1" => "value1" })).unwrap_err();
+        assert!(err.is_io());
+    }
+
+    #[test]
+    fn serialize_size() {
+        #[derive(Serialize)]
+        struct Conn {
+            version: u32,
+            local_addrs: Vec<String>,
+            remote_id: Option<String>,
+            children: Vec<Child>,
+        }
+
+        #[derive(Serialize)]
+        struct Child {
+            mode: String,
+        }
+
+        let data = Conn {
+            version: 2,
+            local_addrs: vec!["192.0.2.1".to_string(), "192.0.2.2".to_string()],
+            remote_id: None,
+            children: vec![
+                Child {
+                    mode: "tunnel".to_string(),
+                },
+                Child {
+                    mode: "transport".to_string(),
+                },
+            ],
+        };
+
+        assert_eq!(serialized_size(&data).unwrap(), to_vec(&data).unwrap().len());
+        assert_eq!(serialized_size(&()).unwrap(), 0);
+    }
+
+    #[test]
+    fn serialize_options() {
+        #[derive(Serialize)]
+        struct Settings {
+            mobike: bool,
+            rate: f64,
+            remote_id: Option<String>,
+            marker: (),
+            pools: Vec<String>,
+        }
+
+        let data = Settings {
+            mobike: false,
+            rate: 1e21,
+            remote_id: None,
+            marker: (),
+            pools: vec![],
+        };
+
+        #[rustfmt::skip]
+        assert_eq!(
+            to_vec(&data).unwrap(),
+            vec![
+                // mobike = no
+                3, 6, b'm', b'o', b'b', b'i', b'k', b'e', 0, 2, b'n', b'o',
+                // rate = 1e21
+                3, 4, b'r', b'a', b't', b'e', 0, 4, b'1', b'e', b'2', b'1',
+                // pools
+                4, 5, b'p', b'o', b'o', b'l', b's',
+                // pools end
+                6,
+            ]
+        );
+
+        let options = SerializerOptions::new()
+            .with_bools("true", String::from("false"))
+            .with_none(Emit::Empty)
+            .with_empty_lists(Emit::Skip)
+            .with_floats(FloatFormat::Plain);
+
+        #[rustfmt::skip]
+        assert_eq!(
+            to_vec_with(&data, &options).unwrap(),
+            vec![
+                // mobike = false
+                3, 6, b'm', b'o', b'b', b'i', b'k', b'e', 0, 5, b'f', b'a', b'l', b's', b'e',
+                // rate = 1000000000000000000000
+                3, 4, b'r', b'a', b't', b'e', 0, 22,
+                b'1', b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'0',
+                b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'0', b'0',
+                // remote_id =
+                3, 9, b'r', b'e', b'm', b'o', b't', b'e', b'_', b'i', b'd', 0, 0,
+                // marker =
+                3, 6, b'm', b'a', b'r', b'k', b'e', b'r', 0, 0,
+            ]
+        );
+
+        assert_eq!(
+            serialized_size_with(&data, &options).unwrap(),
+            to_vec_with(&data, &options).unwrap().len()
+        );
+
+        let options = options.with_floats(FloatFormat::Fixed(1));
+        let actual = to_vec_with(&indexmap! { "rate" => 0.25, "ratio" => 2.0 }, &options).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            actual,
+            vec![
+                // rate = 0.2
+                3, 4, b'r', b'a', b't', b'e', 0, 3, b'0', b'.', b'2',
+                // ratio = 2.0
+                3, 5, b'r', b'a', b't', b'i', b'o', 0, 3, b'2', b'.', b'0',
+            ]
+        );
+        assert_eq!(to_vec_with(&(), &options).unwrap(), vec![]);
+    }
 }
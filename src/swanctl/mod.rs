@@ -0,0 +1,482 @@
+//! Parse the configuration of `swanctl` into the messages `swanctl --load-all` sends to `charon`.
+//!
+//! The configuration is written in the syntax of `swanctl.conf`, which consists of sections, `key = value` pairs, comments, and
+//! `include` statements:
+//!
+//! ```text
+//! connections {
+//!     gw {
+//!         remote_addrs = 192.0.2.1, 192.0.2.2
+//!         local {
+//!             auth = psk
+//!         }
+//!     }
+//! }
+//! include conf.d/*.conf
+//! ```
+//!
+//! Files referenced from the configuration, such as certificates, are read relative to the directory of the configuration in the
+//! same way as `swanctl` does, for example `x509/` for `certs` and `x509ca/` for `cacerts`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{Error, ErrorCode, Result};
+
+#[doc(inline)]
+pub use crate::{
+    swanctl::render::to_swanctl_conf,
+    value::{Section, Value},
+};
+
+mod parser;
+mod render;
+
+/// Keys of connections whose values are comma-separated lists.
+const CONN_LIST_KEYS: &[&str] = &[
+    "local_addrs",
+    "remote_addrs",
+    "proposals",
+    "esp_proposals",
+    "ah_proposals",
+    "local_ts",
+    "remote_ts",
+    "vips",
+    "pools",
+    "groups",
+    "cert_policy",
+];
+
+/// Keys of connections whose values are comma-separated lists of files, and the directories they are read from.
+const CONN_FILE_KEYS: &[(&str, &str)] = &[("certs", "x509"), ("cacerts", "x509ca"), ("pubkeys", "pubkey")];
+
+/// Keys of authorities whose values are comma-separated lists.
+const AUTHORITY_LIST_KEYS: &[&str] = &["crl_uris", "ocsp_uris"];
+
+/// Prefixes of sections in `secrets` that define shared secrets, and their types.
+const SHARED_TYPES: &[(&str, &str)] = &[("eap", "EAP"), ("xauth", "XAUTH"), ("ntlm", "NTLM"), ("ike", "IKE"), ("ppk", "PPK")];
+
+/// A structure representing the messages to load a configuration into `charon`.
+///
+/// Each message is the body of a single command, such as `load-conn` for a connection.
+///
+/// # Example
+///
+/// ```
+/// use anyhow::Result;
+/// use serde_vici::swanctl::Config;
+///
+/// fn main() -> Result<()> {
+///     let config = Config::parse(
+///         r#"
+///         connections {
+///             gw {
+///                 remote_addrs = 192.0.2.1, 192.0.2.2
+///             }
+///         }
+///         "#,
+///     )?;
+///
+///     for (command, message) in config.messages() {
+///         assert_eq!(command, "load-conn");
+///         assert_eq!(message.keys().collect::<Vec<_>>(), vec!["gw"]);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Config {
+    /// The messages of `load-shared`, one for each shared secret.
+    pub shared: Vec<Section>,
+
+    /// The messages of `load-authority`, one for each certification authority.
+    pub authorities: Vec<Section>,
+
+    /// The messages of `load-pool`, one for each pool.
+    pub pools: Vec<Section>,
+
+    /// The messages of `load-conn`, one for each connection.
+    pub conns: Vec<Section>,
+}
+
+/// Parses the syntax of `swanctl.conf` into a tree of sections and values.
+///
+/// Values are kept as they are written, and sections of the same name are merged. Files included with `include` are read relative
+/// to the current directory, where `*` and `?` match any file or directory, and a pattern matching no file includes nothing.
+///
+/// # Errors
+/// Parsing can fail if the input is not valid `swanctl.conf` syntax or an included file cannot be read.
+pub fn parse(input: &str) -> Result<Section> {
+    parse_in(input, Path::new(""))
+}
+
+/// Reads and parses the given `swanctl.conf` into a tree of sections and values.
+///
+/// Files included with `include` are read relative to the directory of the file.
+///
+/// # Errors
+/// Parsing can fail if the file cannot be read, it is not valid `swanctl.conf` syntax, or an included file cannot be read.
+pub fn parse_file(path: impl AsRef<Path>) -> Result<Section> {
+    let path = path.as_ref();
+    let input = fs::read_to_string(path)?;
+    parse_in(&input, path.parent().unwrap_or(Path::new("")))
+}
+
+fn parse_in(input: &str, dir: &Path) -> Result<Section> {
+    let mut section = Section::new();
+    parser::Parser::new(input, dir, 0).parse_into(&mut section)?;
+    Ok(section)
+}
+
+impl Config {
+    /// Parses the syntax of `swanctl.conf` into the messages to load it.
+    ///
+    /// Included and referenced files are read relative to the current directory.
+    ///
+    /// # Errors
+    /// Parsing can fail if the input is not valid `swanctl.conf` syntax or a referenced file cannot be read.
+    pub fn parse(input: &str) -> Result<Self> {
+        Self::from_settings(&parse(input)?, Path::new(""))
+    }
+
+    /// Reads the given `swanctl.conf` into the messages to load it.
+    ///
+    /// Included and referenced files are read relative to the directory of the file.
+    ///
+    /// # Errors
+    /// Loading can fail if the file cannot be read, it is not valid `swanctl.conf` syntax, or a referenced file cannot be read.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_settings(&parse_file(path)?, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Converts a tree of sections and values, as returned by [`parse`], into the messages to load it.
+    ///
+    /// Referenced files are read relative to `dir`. Sections of `secrets` other than shared secrets, such as private keys, are not
+    /// included.
+    ///
+    /// # Errors
+    /// Converting can fail if the tree does not have the structure of `swanctl.conf` or a referenced file cannot be read.
+    pub fn from_settings(settings: &Section, dir: &Path) -> Result<Self> {
+        let mut config = Config::default();
+
+        for (name, secret) in sections(settings, "secrets")? {
+            let Some((_, shared_type)) = SHARED_TYPES.iter().find(|(prefix, _)| name.starts_with(prefix)) else {
+                continue;
+            };
+            config.shared.push(shared(name, shared_type, secret)?);
+        }
+
+        for (name, authority) in sections(settings, "authorities")? {
+            config.authorities.push(named(name, convert_authority(authority, dir)?));
+        }
+
+        for (name, pool) in sections(settings, "pools")? {
+            config.pools.push(named(name, convert_pool(pool)));
+        }
+
+        for (name, conn) in sections(settings, "connections")? {
+            config.conns.push(named(name, convert_conn(conn, dir)?));
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the commands and their messages in the order `swanctl --load-all` sends them.
+    pub fn messages(&self) -> impl Iterator<Item = (&'static str, &Section)> {
+        let shared = self.shared.iter().map(|message| ("load-shared", message));
+        let authorities = self.authorities.iter().map(|message| ("load-authority", message));
+        let pools = self.pools.iter().map(|message| ("load-pool", message));
+        let conns = self.conns.iter().map(|message| ("load-conn", message));
+        shared.chain(authorities).chain(pools).chain(conns)
+    }
+}
+
+fn sections<'a>(settings: &'a Section, name: &str) -> Result<Vec<(&'a str, &'a Section)>> {
+    let Some(value) = settings.get(name) else {
+        return Ok(vec![]);
+    };
+    let section = value.as_section().ok_or_else(|| invalid(format!("'{name}' is not a section")))?;
+
+    section
+        .iter()
+        .map(|(k, v)| match v {
+            Value::Section(v) => Ok((k, v)),
+            _ => Err(invalid(format!("'{name}.{k}' is not a section"))),
+        })
+        .collect()
+}
+
+fn named(name: &str, section: Section) -> Section {
+    [(name, section)].into_iter().collect()
+}
+
+fn shared(name: &str, shared_type: &str, secret: &Section) -> Result<Section> {
+    let data = secret
+        .get("secret")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid(format!("'secrets.{name}.secret' is missing")))?;
+    let owners = secret
+        .iter()
+        .filter(|(k, _)| k.starts_with("id"))
+        .filter_map(|(_, v)| v.as_bytes())
+        .map(<[u8]>::to_vec)
+        .collect();
+
+    let mut message = Section::new();
+    message.insert("id", name);
+    message.insert("type", shared_type);
+    message.insert(
+        "data",
+        decode_secret(data).ok_or_else(|| invalid(format!("'secrets.{name}.secret' is invalid")))?,
+    );
+    message.insert("owners", Value::List(owners));
+    Ok(message)
+}
+
+fn convert_authority(authority: &Section, dir: &Path) -> Result<Section> {
+    let mut message = Section::new();
+    for (k, v) in authority.iter() {
+        match (k, v) {
+            ("cacert", Value::Bytes(v)) => {
+                message.insert("cacert", read_file(&dir.join("x509ca"), v)?);
+            },
+            ("file", Value::Bytes(v)) => {
+                message.insert("cacert", read_file(Path::new(""), v)?);
+            },
+            (k, Value::Bytes(v)) if AUTHORITY_LIST_KEYS.contains(&k) => {
+                message.insert(k, Value::List(split(v)));
+            },
+            (k, v) => {
+                message.insert(k, v.clone());
+            },
+        }
+    }
+    Ok(message)
+}
+
+fn convert_pool(pool: &Section) -> Section {
+    pool.iter()
+        .map(|(k, v)| match (k, v) {
+            ("addrs", v) => (k, v.clone()),
+            (k, Value::Bytes(v)) => (k, Value::List(split(v))),
+            (k, v) => (k, v.clone()),
+        })
+        .collect()
+}
+
+fn convert_conn(conn: &Section, dir: &Path) -> Result<Section> {
+    let mut message = Section::new();
+    for (k, v) in conn.iter() {
+        let value = match v {
+            Value::Section(v) => Value::Section(convert_conn(v, dir)?),
+            Value::Bytes(v) => match CONN_FILE_KEYS.iter().find(|(key, _)| *key == k) {
+                Some((_, sub)) => Value::List(split(v).iter().map(|file| read_file(&dir.join(sub), file)).collect::<Result<_>>()?),
+                None if CONN_LIST_KEYS.contains(&k) => Value::List(split(v)),
+                None => Value::Bytes(v.clone()),
+            },
+            Value::List(_) => v.clone(),
+        };
+        message.insert(k, value);
+    }
+    Ok(message)
+}
+
+fn split(value: &[u8]) -> Vec<Vec<u8>> {
+    value
+        .split(|&b| b == b',')
+        .map(|item| item.trim_ascii().to_vec())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn read_file(dir: &Path, file: &[u8]) -> Result<Vec<u8>> {
+    let file = std::str::from_utf8(file).map_err(|_| invalid("file name is not valid UTF-8".to_string()))?;
+    let path: PathBuf = dir.join(file);
+    fs::read(&path).map_err(|e| invalid(format!("failed to read '{}': {e}", path.display())))
+}
+
+/// Decodes a secret, which is either a string, hexadecimal with the prefix `0x`, or Base64 with the prefix `0s`.
+fn decode_secret(data: &str) -> Option<Vec<u8>> {
+    if let Some(hex) = data.strip_prefix("0x") {
+        let hex = hex.as_bytes();
+        if hex.len() % 2 != 0 {
+            return None;
+        }
+        return hex
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+            .collect();
+    }
+
+    if let Some(base64) = data.strip_prefix("0s") {
+        let mut decoded = vec![];
+        let (mut buf, mut bits) = (0u32, 0);
+        for c in base64.bytes().take_while(|&c| c != b'=') {
+            let v = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return None,
+            };
+            buf = buf << 6 | u32::from(v);
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                decoded.push((buf >> bits) as u8);
+            }
+        }
+        return Some(decoded);
+    }
+
+    Some(data.as_bytes().to_vec())
+}
+
+fn invalid(msg: String) -> Error {
+    Error::data(ErrorCode::Message(msg), None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn list(items: &[&str]) -> Value {
+        Value::List(items.iter().map(|item| item.as_bytes().to_vec()).collect())
+    }
+
+    #[test]
+    fn load_messages() {
+        let dir = std::env::temp_dir().join(format!("serde-vici-swanctl-{}", std::process::id()));
+        fs::create_dir_all(dir.join("x509ca")).unwrap();
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        fs::write(dir.join("x509ca/ca.pem"), "CA").unwrap();
+        fs::write(
+            dir.join("conf.d/pools.conf"),
+            "pools {\n    rw {\n        addrs = 10.0.0.0/24\n        dns = 192.0.2.53, 192.0.2.54\n    }\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("swanctl.conf"),
+            r#"
+            connections {
+                gw {
+                    remote_addrs = 192.0.2.1, 192.0.2.2
+                    version = 2
+                    local {
+                        auth = psk
+                        id = gw.example.com
+                    }
+                    remote {
+                        cacerts = ca.pem
+                    }
+                    children {
+                        net {
+                            local_ts = 10.0.0.0/24
+                            esp_proposals = aes256gcm16, default
+                        }
+                    }
+                }
+            }
+            secrets {
+                ike-gw {
+                    id = gw.example.com
+                    id-remote = 192.0.2.1
+                    secret = 0x6b6579
+                }
+                eap-user {
+                    secret = 0sa2V5
+                }
+                private-gw {
+                    file = gw.pem
+                }
+            }
+            authorities {
+                ca {
+                    cacert = ca.pem
+                    crl_uris = http://crl.example.com/ca.crl
+                }
+            }
+            include conf.d/*.conf
+            "#,
+        )
+        .unwrap();
+
+        let actual = Config::load(dir.join("swanctl.conf"));
+        fs::remove_dir_all(&dir).unwrap();
+        let actual = actual.unwrap();
+
+        let local: Section = [("auth", "psk"), ("id", "gw.example.com")].into_iter().collect();
+        let remote: Section = [("cacerts", list(&["CA"]))].into_iter().collect();
+        let net: Section = [
+            ("local_ts", list(&["10.0.0.0/24"])),
+            ("esp_proposals", list(&["aes256gcm16", "default"])),
+        ]
+        .into_iter()
+        .collect();
+        let gw: Section = [
+            ("remote_addrs", list(&["192.0.2.1", "192.0.2.2"])),
+            ("version", Value::from("2")),
+            ("local", Value::Section(local)),
+            ("remote", Value::Section(remote)),
+            ("children", Value::Section(named("net", net))),
+        ]
+        .into_iter()
+        .collect();
+
+        let ike: Section = [
+            ("id", Value::from("ike-gw")),
+            ("type", Value::from("IKE")),
+            ("data", Value::from("key")),
+            ("owners", list(&["gw.example.com", "192.0.2.1"])),
+        ]
+        .into_iter()
+        .collect();
+        let eap: Section = [
+            ("id", Value::from("eap-user")),
+            ("type", Value::from("EAP")),
+            ("data", Value::from("key")),
+            ("owners", list(&[])),
+        ]
+        .into_iter()
+        .collect();
+
+        let ca: Section = [
+            ("cacert", Value::from("CA")),
+            ("crl_uris", list(&["http://crl.example.com/ca.crl"])),
+        ]
+        .into_iter()
+        .collect();
+        let rw: Section = [("addrs", Value::from("10.0.0.0/24")), ("dns", list(&["192.0.2.53", "192.0.2.54"]))]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            actual,
+            Config {
+                shared: vec![ike, eap],
+                authorities: vec![named("ca", ca)],
+                pools: vec![named("rw", rw)],
+                conns: vec![named("gw", gw)],
+            }
+        );
+        assert_eq!(
+            actual.messages().map(|(command, _)| command).collect::<Vec<_>>(),
+            vec!["load-shared", "load-shared", "load-authority", "load-pool", "load-conn"]
+        );
+    }
+
+    #[test]
+    fn load_invalid() {
+        let err = Config::parse("connections {\n    gw = 192.0.2.1\n}\n").unwrap_err();
+        assert_eq!(err.to_string(), "'connections.gw' is not a section");
+
+        let err = Config::parse("secrets {\n    ike {\n        secret = 0xabc\n    }\n}\n").unwrap_err();
+        assert_eq!(err.to_string(), "'secrets.ike.secret' is invalid");
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, ErrorCode, Result},
+    value::{Section, Value},
+};
+
+/// The maximum depth of nested `include` statements, which prevents files from including themselves indefinitely.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+pub(crate) struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    dir: &'a Path,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str, dir: &'a Path, depth: usize) -> Self {
+        let pos = 0;
+        let line = 1;
+        Self {
+            input,
+            pos,
+            line,
+            dir,
+            depth,
+        }
+    }
+
+    /// Parses the whole input into the given section, merging sections of the same name.
+    pub fn parse_into(&mut self, section: &mut Section) -> Result<()> {
+        self.parse_section(section, false)
+    }
+
+    fn parse_section(&mut self, section: &mut Section, nested: bool) -> Result<()> {
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None if nested => return Err(self.error("missing '}' at end of input")),
+                None => return Ok(()),
+                Some('}') if nested => {
+                    self.bump();
+                    return Ok(());
+                },
+                Some('}') => return Err(self.error("unexpected '}'")),
+                Some(_) => {},
+            }
+
+            let name = self.parse_name()?;
+            self.skip_blank();
+            match self.peek() {
+                Some('{') => {
+                    self.bump();
+                    if !matches!(section.get(name), Some(Value::Section(_))) {
+                        section.insert(name, Section::new());
+                    }
+                    let child = section.get_mut(name).and_then(Value::as_section_mut).unwrap();
+                    self.parse_section(child, true)?;
+                },
+                Some('=') => {
+                    self.bump();
+                    let value = self.parse_value()?;
+                    section.insert(name, value);
+                },
+                Some(_) if name == "include" => {
+                    let pattern = self.parse_value()?;
+                    self.include(&pattern, section)?;
+                },
+                _ => return Err(self.error(&format!("expected '=' or '{{' after '{name}'"))),
+            }
+        }
+    }
+
+    fn parse_name(&mut self) -> Result<&'a str> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || matches!(c, '{' | '}' | '=' | '#' | '"') {
+                break;
+            }
+            self.bump();
+        }
+
+        match &self.input[start..self.pos] {
+            "" => Err(self.error("expected a name")),
+            name => Ok(name),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<String> {
+        self.skip_blank();
+        if self.peek() == Some('"') {
+            self.bump();
+            return self.parse_quoted();
+        }
+
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if matches!(c, '\n' | '#' | '}') {
+                break;
+            }
+            self.bump();
+        }
+        Ok(self.input[start..self.pos].trim().to_string())
+    }
+
+    fn parse_quoted(&mut self) -> Result<String> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error("missing '\"' at end of input")),
+                Some('"') => return Ok(value),
+                Some('\\') => match self.bump() {
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some('\n') => {},
+                    Some(c) => value.push(c),
+                    None => return Err(self.error("missing '\"' at end of input")),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn include(&mut self, pattern: &str, section: &mut Section) -> Result<()> {
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(self.error(&format!("too many nested includes of '{pattern}'")));
+        }
+
+        for path in glob(&self.dir.join(pattern))? {
+            let input = fs::read_to_string(&path)?;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            Parser::new(&input, dir, self.depth + 1).parse_into(section)?;
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                },
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() && c != '\n' => {
+                    self.bump();
+                },
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while !matches!(self.peek(), None | Some('\n')) {
+            self.bump();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::data(ErrorCode::Message(format!("{msg} at line {}", self.line)), None, None)
+    }
+}
+
+/// Expands the wildcards `*` and `?` in any component of the given pattern, returning the matching files in order.
+///
+/// As with `glob(3)`, a pattern matching no file, including a path without wildcards that does not exist or is a directory, matches
+/// nothing, and wildcards do not match a leading `.` in a name.
+fn glob(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let name = component.as_os_str();
+        let Some(wildcard) = name.to_str().filter(|name| name.contains(['*', '?'])) else {
+            paths.iter_mut().for_each(|path| path.push(name));
+            continue;
+        };
+
+        let mut matched = vec![];
+        for path in paths {
+            let dir = if path.as_os_str().is_empty() { Path::new(".") } else { &path };
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) if !dir.is_dir() => continue,
+                Err(e) => return Err(e.into()),
+            };
+            for entry in entries {
+                let file_name = entry?.file_name();
+                if file_name.to_str().is_some_and(|file| {
+                    (!file.starts_with('.') || wildcard.starts_with('.')) && matches(wildcard.as_bytes(), file.as_bytes())
+                }) {
+                    matched.push(path.join(file_name));
+                }
+            }
+        }
+        paths = matched;
+    }
+
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    Ok(paths)
+}
+
+fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.split_first(), name.split_first()) {
+        (None, None) => true,
+        (Some((b'*', rest)), _) => matches(rest, name) || (!name.is_empty() && matches(pattern, &name[1..])),
+        (Some((b'?', rest)), Some((_, name))) => matches(rest, name),
+        (Some((p, rest)), Some((n, name))) => p == n && matches(rest, name),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse(input: &str) -> Result<Section> {
+        let mut section = Section::new();
+        Parser::new(input, Path::new("."), 0).parse_into(&mut section)?;
+        Ok(section)
+    }
+
+    #[test]
+    fn parse_settings() {
+        let actual = parse(
+            r#"
+            # comment
+            connections {
+                gw {
+                    remote_addrs = 192.0.2.1, 192.0.2.2 # trailing comment
+                    local { auth = psk }
+                }
+            }
+            connections {
+                gw {
+                    version = 2
+                    empty =
+                }
+            }
+            secrets {
+                ike-gw {
+                    secret = "quoted # \"secret\""
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let gw: Section = [
+            ("remote_addrs", Value::from("192.0.2.1, 192.0.2.2")),
+            ("local", Value::Section([("auth", "psk")].into_iter().collect())),
+            ("version", Value::from("2")),
+            ("empty", Value::from("")),
+        ]
+        .into_iter()
+        .collect();
+        let ike: Section = [("secret", "quoted # \"secret\"")].into_iter().collect();
+        let expected: Section = [
+            ("connections", Value::Section([("gw", gw)].into_iter().collect())),
+            ("secrets", Value::Section([("ike-gw", ike)].into_iter().collect())),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for (input, msg) in [
+            ("connections {\n", "missing '}' at end of input at line 2"),
+            ("}", "unexpected '}' at line 1"),
+            ("\nkey value", "expected '=' or '{' after 'key' at line 2"),
+            ("key = \"value", "missing '\"' at end of input at line 1"),
+        ] {
+            assert_eq!(parse(input).unwrap_err().to_string(), msg);
+        }
+    }
+
+    #[test]
+    fn glob_matches() {
+        assert!(matches(b"*.conf", b"gw.conf"));
+        assert!(matches(b"gw-??.conf", b"gw-01.conf"));
+        assert!(!matches(b"*.conf", b"gw.conf.bak"));
+        assert!(!matches(b"gw-?.conf", b"gw-01.conf"));
+    }
+
+    #[test]
+    fn glob_directories() {
+        let dir = std::env::temp_dir().join(format!("serde-vici-glob-{}", std::process::id()));
+        for file in [
+            "a/conf.d/gw.conf",
+            "b/conf.d/rw.conf",
+            "b/conf.d/.hidden.conf",
+            "c/conf.d/notes.txt",
+        ] {
+            fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let actual = (
+            glob(&dir.join("*/conf.d/*.conf")),
+            glob(&dir.join("*/missing/*.conf")),
+            glob(&dir.join("a/conf.d")),
+            glob(&dir.join("a/missing.conf")),
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(actual.0.unwrap(), vec![dir.join("a/conf.d/gw.conf"), dir.join("b/conf.d/rw.conf")]);
+        assert_eq!(actual.1.unwrap(), Vec::<PathBuf>::new());
+        assert_eq!(actual.2.unwrap(), Vec::<PathBuf>::new());
+        assert_eq!(actual.3.unwrap(), Vec::<PathBuf>::new());
+    }
+}
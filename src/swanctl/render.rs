@@ -0,0 +1,250 @@
+use std::{fmt::Write, str};
+
+use serde::Serialize;
+
+use crate::{
+    error::{Error, ErrorCode, Result},
+    value::{to_value, Section, Value},
+};
+
+/// Serialize the given data structure as the text of `swanctl.conf`.
+///
+/// The data structure is interpreted in the same way as the [`Serializer`](crate::Serializer) does: structs and maps become nested
+/// blocks, lists become comma-separated values, and `None` values and empty lists are omitted, as an empty value is read back as
+/// a list of one empty item. Values containing whitespace or characters that have a meaning in the syntax are quoted.
+///
+/// The output can be parsed back with [`parse`](crate::swanctl::parse) or loaded with [`Config`](crate::swanctl::Config).
+///
+/// # Example
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use anyhow::Result;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Conn {
+///     remote_addrs: Vec<String>,
+///     version: u32,
+/// }
+///
+/// fn main() -> Result<()> {
+///     let conn = Conn {
+///         remote_addrs: vec!["192.0.2.1".to_string(), "192.0.2.2".to_string()],
+///         version: 2,
+///     };
+///     let connections = BTreeMap::from([("connections", BTreeMap::from([("gw", conn)]))]);
+///
+///     let conf = serde_vici::swanctl::to_swanctl_conf(&connections)?;
+///     assert_eq!(
+///         conf,
+///         "connections {\n    gw {\n        remote_addrs = 192.0.2.1, 192.0.2.2\n        version = 2\n    }\n}\n",
+///     );
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to fail, if `T` cannot be represented as a VICI section, or
+/// if a key cannot be written unquoted, a value is not valid UTF-8, or a list item contains a comma.
+pub fn to_swanctl_conf<T>(value: &T) -> Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let mut output = String::new();
+    if let Value::Section(section) = to_value(value)? {
+        render_section(&mut output, &section, 0)?;
+    }
+    Ok(output)
+}
+
+fn render_section(output: &mut String, section: &Section, depth: usize) -> Result<()> {
+    for (key, value) in section.iter() {
+        // Keys cannot be quoted, so those the parser would read differently are rejected.
+        if key.is_empty() || key.contains(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '=' | '"' | '#' | '{' | '}' | ',')) {
+            return Err(invalid(format!("key '{key}' cannot be written")));
+        }
+
+        let indent = "    ".repeat(depth);
+        match value {
+            Value::Bytes(v) => {
+                let value = text(key, v)?;
+                if needs_quote(value) {
+                    let _ = writeln!(output, "{indent}{key} = {}", quote(value));
+                } else {
+                    let _ = writeln!(output, "{indent}{key} = {value}");
+                }
+            },
+            Value::List(items) if items.is_empty() => {},
+            Value::List(items) => {
+                let items = items
+                    .iter()
+                    .map(|item| match text(key, item)? {
+                        item if item.contains(',') => Err(invalid(format!("list item of '{key}' contains a comma"))),
+                        item => Ok(item),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let value = items.join(", ");
+                if items.iter().any(|item| needs_quote(item)) {
+                    let _ = writeln!(output, "{indent}{key} = {}", quote(&value));
+                } else {
+                    let _ = writeln!(output, "{indent}{key} = {value}");
+                }
+            },
+            Value::Section(v) => {
+                let _ = writeln!(output, "{indent}{key} {{");
+                render_section(output, v, depth + 1)?;
+                let _ = writeln!(output, "{indent}}}");
+            },
+        }
+    }
+    Ok(())
+}
+
+fn text<'a>(key: &str, value: &'a [u8]) -> Result<&'a str> {
+    str::from_utf8(value).map_err(|_| invalid(format!("value of '{key}' is not valid UTF-8")))
+}
+
+fn needs_quote(value: &str) -> bool {
+    value.is_empty() || value.contains(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '"' | '#' | '{' | '}' | '\\'))
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn invalid(msg: String) -> Error {
+    Error::data(ErrorCode::Message(msg), None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use pretty_assertions::assert_eq;
+    use serde_derive::Serialize;
+
+    use super::*;
+    use crate::swanctl::{parse, Config};
+
+    #[derive(Serialize)]
+    struct Conn<'a> {
+        remote_addrs: Vec<&'a str>,
+        version: u32,
+        mobike: bool,
+        local: Auth<'a>,
+        children: BTreeMap<&'a str, Child<'a>>,
+    }
+
+    #[derive(Serialize)]
+    struct Auth<'a> {
+        auth: &'a str,
+        id: Option<&'a str>,
+    }
+
+    #[derive(Serialize)]
+    struct Child<'a> {
+        local_ts: Vec<&'a str>,
+        esp_proposals: Vec<&'a str>,
+        updown: &'a str,
+    }
+
+    fn connections() -> BTreeMap<&'static str, BTreeMap<&'static str, Conn<'static>>> {
+        let conn = Conn {
+            remote_addrs: vec!["192.0.2.1", "192.0.2.2"],
+            version: 2,
+            mobike: false,
+            local: Auth {
+                auth: "psk",
+                id: Some("C=JP, O=Example, CN=gw.example.com"),
+            },
+            children: BTreeMap::from([(
+                "net",
+                Child {
+                    local_ts: vec![],
+                    esp_proposals: vec!["aes256gcm16", "default"],
+                    updown: "/usr/local/libexec/updown \"iptables\" # {}",
+                },
+            )]),
+        };
+        BTreeMap::from([("connections", BTreeMap::from([("gw", conn)]))])
+    }
+
+    #[test]
+    fn render() {
+        let actual = to_swanctl_conf(&connections()).unwrap();
+        assert_eq!(
+            actual,
+            r#"connections {
+    gw {
+        remote_addrs = 192.0.2.1, 192.0.2.2
+        version = 2
+        mobike = no
+        local {
+            auth = psk
+            id = "C=JP, O=Example, CN=gw.example.com"
+        }
+        children {
+            net {
+                esp_proposals = aes256gcm16, default
+                updown = "/usr/local/libexec/updown \"iptables\" # {}"
+            }
+        }
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let conf = to_swanctl_conf(&connections()).unwrap();
+
+        let Value::Section(mut expected) = to_value(&connections()["connections"]).unwrap() else {
+            unreachable!();
+        };
+
+        // Empty lists are omitted.
+        let gw = expected.get_mut("gw").and_then(Value::as_section_mut).unwrap();
+        let children = gw.get_mut("children").and_then(Value::as_section_mut).unwrap();
+        children
+            .get_mut("net")
+            .and_then(Value::as_section_mut)
+            .unwrap()
+            .remove("local_ts")
+            .unwrap();
+        let actual = Config::parse(&conf).unwrap();
+        assert_eq!(actual.conns, vec![expected]);
+
+        let expected = parse(&conf).unwrap();
+        assert_eq!(parse(&to_swanctl_conf(&expected).unwrap()).unwrap(), expected);
+    }
+
+    #[test]
+    fn render_invalid() {
+        let err = to_swanctl_conf(&BTreeMap::from([("pools", vec!["a,b"])])).unwrap_err();
+        assert_eq!(err.to_string(), "list item of 'pools' contains a comma");
+
+        let err = to_swanctl_conf(&BTreeMap::from([("data", serde_bytes::Bytes::new(&[0xff]))])).unwrap_err();
+        assert_eq!(err.to_string(), "value of 'data' is not valid UTF-8");
+
+        for key in ["", "a b", "a=b", "a{", "a#b"] {
+            let err = to_swanctl_conf(&BTreeMap::from([(key, "value")])).unwrap_err();
+            assert_eq!(err.to_string(), format!("key '{key}' cannot be written"));
+        }
+    }
+}
@@ -0,0 +1,696 @@
+//! In-process VICI servers for testing applications without a running `charon`.
+//!
+//! # Example
+//!
+//! ```
+//! use anyhow::Result;
+//! use serde::{Deserialize, Serialize};
+//! use serde_vici::{
+//!     packet::{read_packet, write_packet, Packet, PacketType},
+//!     testing::{MockServer, Reply},
+//!     transport::Stream,
+//! };
+//!
+//! #[derive(Deserialize, Serialize)]
+//! struct Version {
+//!     daemon: String,
+//!     version: String,
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let server = MockServer::bind_tcp()?;
+//!     server.handle("version", |_| {
+//!         Reply::new(&Version {
+//!             daemon: "charon".to_string(),
+//!             version: "5.9.5".to_string(),
+//!         })
+//!     });
+//!
+//!     let mut stream = Stream::connect(server.endpoint())?;
+//!     write_packet(&mut stream, &Packet::request("version", &())?)?;
+//!
+//!     let packet = read_packet(&mut stream)?;
+//!     assert_eq!(packet.packet_type, PacketType::CmdResponse);
+//!     assert_eq!(packet.deserialize::<Version>()?.version, "5.9.5");
+//!     assert_eq!(server.requests()[0].command, "version");
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use serde::{de, ser};
+
+use crate::{
+    de::from_slice,
+    error::Result,
+    packet::{read_packet, write_packet, Packet, PacketType},
+    record::{Direction, Recording},
+    transport::{Endpoint, Listener, Stream},
+};
+
+type Handler = Arc<dyn Fn(&Request) -> Result<Reply> + Send + Sync>;
+
+/// How long to wait before accepting connections again after accepting failed.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+
+/// An in-process VICI server that replies to commands with registered handlers.
+///
+/// The server accepts any number of connections, each served on its own thread, and stops when dropped.
+pub struct MockServer {
+    endpoint: Endpoint,
+    shared: Arc<Shared>,
+    accept: Option<JoinHandle<()>>,
+}
+
+/// A command request received by the [`MockServer`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Request {
+    /// The name of the command.
+    pub command: String,
+
+    /// The message of the request encoded as VICI.
+    pub body: Vec<u8>,
+}
+
+/// A reply to a command, consisting of the streamed events followed by the response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reply {
+    events: Vec<Packet>,
+    delay: Option<Duration>,
+    response: Packet,
+}
+
+/// A VICI server that plays a recording back to every client connecting to it.
+///
+/// Frames sent by the recorded side are expected from the client and compared with the packets actually received, while frames
+/// received by the recorded side are written to the client. Timestamps are ignored, and the connection is closed at the end of the
+/// recording.
+pub struct ReplayServer {
+    endpoint: Endpoint,
+    shared: Arc<ReplayShared>,
+    accept: Option<JoinHandle<()>>,
+}
+
+/// A packet received by the [`ReplayServer`] that differs from the recording.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mismatch {
+    /// The index of the frame in the recording.
+    pub index: usize,
+
+    /// The packet in the recording.
+    pub expected: Packet,
+
+    /// The packet actually received.
+    pub actual: Packet,
+}
+
+struct Shared {
+    handlers: Mutex<HashMap<String, Handler>>,
+    events: Mutex<Option<HashSet<String>>>,
+    requests: Mutex<Vec<Request>>,
+    connections: Mutex<Vec<Arc<Connection>>>,
+    closed: AtomicBool,
+}
+
+struct ReplayShared {
+    recording: Recording,
+    mismatches: Mutex<Vec<Mismatch>>,
+    connections: Mutex<Vec<Arc<Stream>>>,
+    closed: AtomicBool,
+}
+
+struct Connection {
+    writer: Mutex<Stream>,
+    events: Mutex<HashSet<String>>,
+}
+
+impl MockServer {
+    /// Starts a server listening on an ephemeral port of the TCP loopback.
+    pub fn bind_tcp() -> io::Result<Self> {
+        let (listener, endpoint) = Listener::bind_tcp("127.0.0.1:0")?;
+        Ok(Self::start(listener, endpoint))
+    }
+
+    /// Starts a server listening on a Unix domain socket at the given path, replacing an existing socket.
+    #[cfg(unix)]
+    pub fn bind_unix(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let (listener, endpoint) = Listener::bind_unix(path)?;
+        Ok(Self::start(listener, endpoint))
+    }
+
+    fn start(listener: Listener, endpoint: Endpoint) -> Self {
+        let shared = Arc::new(Shared {
+            handlers: Mutex::new(HashMap::new()),
+            events: Mutex::new(None),
+            requests: Mutex::new(vec![]),
+            connections: Mutex::new(vec![]),
+            closed: AtomicBool::new(false),
+        });
+
+        let accept = {
+            let shared = shared.clone();
+            thread::spawn(move || shared.accept(listener))
+        };
+
+        Self {
+            endpoint,
+            shared,
+            accept: Some(accept),
+        }
+    }
+
+    /// Limits the events that connections can register for, answering the registration of any other event with `EVENT_UNKNOWN`.
+    ///
+    /// Connections can register for any event by default.
+    pub fn with_events<I>(self, events: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        *self.shared.events.lock().unwrap() = Some(events.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Returns the endpoint this server listens on.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Registers a handler for the given command, replacing the previous one.
+    ///
+    /// Commands without a handler are answered with `CMD_UNKNOWN`. If the handler returns an error, the connection is closed.
+    pub fn handle<F>(&self, command: &str, handler: F)
+    where
+        F: Fn(&Request) -> Result<Reply> + Send + Sync + 'static,
+    {
+        let mut handlers = self.shared.handlers.lock().unwrap();
+        handlers.insert(command.to_string(), Arc::new(handler));
+    }
+
+    /// Sends an event to every connection registered for it.
+    ///
+    /// # Errors
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+    pub fn emit<T>(&self, event: &str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let packet = Packet::event(event, value)?;
+        let connections = self.shared.connections.lock().unwrap().clone();
+        for connection in connections {
+            // Connections that went away in the meantime are not an error of the caller.
+            let _ = connection.send_event(&packet);
+        }
+        Ok(())
+    }
+
+    /// Returns the command requests received so far, in order.
+    pub fn requests(&self) -> Vec<Request> {
+        self.shared.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the number of connections currently open.
+    pub fn connections(&self) -> usize {
+        self.shared.connections.lock().unwrap().len()
+    }
+
+    /// Returns the events the open connections are registered for, sorted and without duplicates.
+    pub fn registered(&self) -> Vec<String> {
+        let connections = self.shared.connections.lock().unwrap().clone();
+        let mut events: Vec<_> = connections
+            .iter()
+            .flat_map(|connection| connection.events.lock().unwrap().iter().cloned().collect::<Vec<_>>())
+            .collect();
+        events.sort();
+        events.dedup();
+        events
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+
+        // Wake up the accepting thread so that it can observe the flag.
+        let _ = Stream::connect(&self.endpoint);
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+
+        for connection in self.shared.connections.lock().unwrap().drain(..) {
+            let _ = connection.writer.lock().unwrap().shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Request {
+    /// Deserializes the message of this request into an instance of type `T`.
+    ///
+    /// # Errors
+    /// Deserialization can fail if the structure of the message does not match the structure expected by `T`.
+    pub fn deserialize<'a, T>(&'a self) -> Result<T>
+    where
+        T: de::Deserialize<'a>,
+    {
+        from_slice(&self.body)
+    }
+}
+
+impl Reply {
+    /// Creates a reply whose response is serialized from the given value.
+    ///
+    /// # Errors
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+    pub fn new<T>(value: &T) -> Result<Self>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let events = vec![];
+        let delay = None;
+        let response = Packet::response(value)?;
+        Ok(Self { events, delay, response })
+    }
+
+    /// Appends an event streamed before the response, which is only sent if the connection is registered for it.
+    ///
+    /// # Errors
+    /// Serialization can fail if `T`'s implementation of `Serialize` decides to return an error.
+    pub fn with_event<T>(mut self, event: &str, value: &T) -> Result<Self>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.events.push(Packet::event(event, value)?);
+        Ok(self)
+    }
+
+    /// Delays the response by the given duration after the events are sent, to test how clients handle slow commands.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+impl Shared {
+    fn accept(self: Arc<Self>, listener: Listener) {
+        let closed = &self.closed;
+        accept(&listener, closed, |stream| {
+            let writer = stream.try_clone()?;
+            let connection = Arc::new(Connection {
+                writer: Mutex::new(writer),
+                events: Mutex::new(HashSet::new()),
+            });
+            self.connections.lock().unwrap().push(connection.clone());
+
+            let shared = self.clone();
+            thread::spawn(move || {
+                let _ = shared.serve(&connection, stream);
+                shared.connections.lock().unwrap().retain(|c| !Arc::ptr_eq(c, &connection));
+            });
+            Ok(())
+        });
+    }
+
+    fn serve(&self, connection: &Connection, mut reader: Stream) -> Result<()> {
+        loop {
+            let packet = read_packet(&mut reader)?;
+            let name = packet.name.unwrap_or_default();
+            match packet.packet_type {
+                PacketType::CmdRequest => {
+                    let request = Request {
+                        command: name,
+                        body: packet.body,
+                    };
+                    self.requests.lock().unwrap().push(request.clone());
+
+                    // The handler is called without holding the lock, so that it can register other handlers.
+                    let handler = self.handlers.lock().unwrap().get(&request.command).cloned();
+                    let reply = handler.map(|handler| handler(&request)).transpose()?;
+
+                    match reply {
+                        Some(reply) => {
+                            for event in &reply.events {
+                                connection.send_event(event)?;
+                            }
+                            if let Some(delay) = reply.delay {
+                                thread::sleep(delay);
+                            }
+                            connection.send(&reply.response)?;
+                        },
+                        None => connection.send(&Packet::new(PacketType::CmdUnknown, None, vec![]))?,
+                    }
+                },
+                PacketType::EventRegister | PacketType::EventUnregister if !self.is_known(&name) => {
+                    connection.send(&Packet::new(PacketType::EventUnknown, None, vec![]))?;
+                },
+                PacketType::EventRegister => {
+                    connection.events.lock().unwrap().insert(name);
+                    connection.send(&Packet::new(PacketType::EventConfirm, None, vec![]))?;
+                },
+                PacketType::EventUnregister => {
+                    connection.events.lock().unwrap().remove(&name);
+                    connection.send(&Packet::new(PacketType::EventConfirm, None, vec![]))?;
+                },
+                _ => {
+                    return Err(io::Error::from(io::ErrorKind::InvalidData).into());
+                },
+            }
+        }
+    }
+
+    fn is_known(&self, event: &str) -> bool {
+        self.events.lock().unwrap().as_ref().is_none_or(|events| events.contains(event))
+    }
+}
+
+impl ReplayServer {
+    /// Starts a server replaying the recording on an ephemeral port of the TCP loopback.
+    pub fn bind_tcp(recording: Recording) -> io::Result<Self> {
+        let (listener, endpoint) = Listener::bind_tcp("127.0.0.1:0")?;
+        Ok(Self::start(recording, listener, endpoint))
+    }
+
+    /// Starts a server replaying the recording on a Unix domain socket at the given path, replacing an existing socket.
+    #[cfg(unix)]
+    pub fn bind_unix(recording: Recording, path: impl Into<PathBuf>) -> io::Result<Self> {
+        let (listener, endpoint) = Listener::bind_unix(path)?;
+        Ok(Self::start(recording, listener, endpoint))
+    }
+
+    fn start(recording: Recording, listener: Listener, endpoint: Endpoint) -> Self {
+        let shared = Arc::new(ReplayShared {
+            recording,
+            mismatches: Mutex::new(vec![]),
+            connections: Mutex::new(vec![]),
+            closed: AtomicBool::new(false),
+        });
+
+        let accept = {
+            let shared = shared.clone();
+            thread::spawn(move || shared.accept(listener))
+        };
+
+        Self {
+            endpoint,
+            shared,
+            accept: Some(accept),
+        }
+    }
+
+    /// Returns the endpoint this server listens on.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Returns the packets received so far that did not match the recording, in order.
+    pub fn mismatches(&self) -> Vec<Mismatch> {
+        self.shared.mismatches.lock().unwrap().clone()
+    }
+}
+
+impl Drop for ReplayServer {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+
+        // Wake up the accepting thread so that it can observe the flag.
+        let _ = Stream::connect(&self.endpoint);
+        if let Some(accept) = self.accept.take() {
+            let _ = accept.join();
+        }
+
+        for connection in self.shared.connections.lock().unwrap().drain(..) {
+            let _ = connection.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl ReplayShared {
+    fn accept(self: Arc<Self>, listener: Listener) {
+        let closed = &self.closed;
+        accept(&listener, closed, |stream| {
+            let connection = Arc::new(stream.try_clone()?);
+            self.connections.lock().unwrap().push(connection.clone());
+
+            let shared = self.clone();
+            thread::spawn(move || {
+                let _ = shared.serve(stream);
+                shared.connections.lock().unwrap().retain(|c| !Arc::ptr_eq(c, &connection));
+            });
+            Ok(())
+        });
+    }
+
+    fn serve(&self, mut stream: Stream) -> Result<()> {
+        for (index, frame) in self.recording.frames.iter().enumerate() {
+            let expected = frame.packet();
+            match frame.direction {
+                Direction::Sent => {
+                    let actual = read_packet(&mut stream)?;
+                    if actual != expected {
+                        self.mismatches.lock().unwrap().push(Mismatch { index, expected, actual });
+                    }
+                },
+                Direction::Received => {
+                    write_packet(&mut stream, &expected)?;
+                },
+            }
+        }
+
+        stream.shutdown(Shutdown::Both)?;
+        Ok(())
+    }
+}
+
+fn accept<F>(listener: &Listener, closed: &AtomicBool, mut serve: F)
+where
+    F: FnMut(Stream) -> io::Result<()>,
+{
+    loop {
+        let stream = listener.accept();
+        if closed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        match stream {
+            Ok(stream) => {
+                let _ = serve(stream);
+            },
+            // Errors such as running out of file descriptors persist for a while, so retrying right away would only spin.
+            Err(_) => thread::sleep(ACCEPT_BACKOFF),
+        }
+    }
+}
+
+impl Connection {
+    fn send(&self, packet: &Packet) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        write_packet(&mut *writer, packet)
+    }
+
+    fn send_event(&self, packet: &Packet) -> Result<()> {
+        let registered = packet.name.as_ref().is_some_and(|name| self.events.lock().unwrap().contains(name));
+
+        if registered {
+            self.send(packet)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::record::Recorder;
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    struct Sa {
+        state: String,
+    }
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    struct Ike {
+        ike: String,
+    }
+
+    fn request(stream: &mut (impl io::Read + io::Write), packet: &Packet) -> Packet {
+        write_packet(stream, packet).unwrap();
+        read_packet(stream).unwrap()
+    }
+
+    fn eventually(condition: impl Fn() -> bool) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn unknown_command() {
+        let server = MockServer::bind_tcp().unwrap();
+        let mut stream = Stream::connect(server.endpoint()).unwrap();
+
+        let actual = request(&mut stream, &Packet::request("version", &()).unwrap());
+        assert_eq!(actual, Packet::new(PacketType::CmdUnknown, None, vec![]));
+        assert_eq!(
+            server.requests(),
+            vec![Request {
+                command: "version".to_string(),
+                body: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn handler_registers_handler() {
+        let server = Arc::new(MockServer::bind_tcp().unwrap());
+        let weak = Arc::downgrade(&server);
+        server.handle("load-conn", move |_| {
+            if let Some(server) = weak.upgrade() {
+                server.handle("get-conns", |_| Reply::new(&()));
+            }
+            Reply::new(&())
+        });
+        let mut stream = Stream::connect(server.endpoint()).unwrap();
+
+        let actual = request(&mut stream, &Packet::request("load-conn", &()).unwrap());
+        assert_eq!(actual, Packet::response(&()).unwrap());
+        let actual = request(&mut stream, &Packet::request("get-conns", &()).unwrap());
+        assert_eq!(actual, Packet::response(&()).unwrap());
+    }
+
+    #[test]
+    fn streamed_events() {
+        let server = MockServer::bind_tcp().unwrap();
+        server.handle("list-sas", |request| {
+            let ike: Ike = request.deserialize()?;
+            Reply::new(&())?.with_event(
+                "list-sa",
+                &indexmap::indexmap! {
+                    ike.ike => Sa {
+                        state: "ESTABLISHED".to_string(),
+                    },
+                },
+            )
+        });
+
+        let mut stream = Stream::connect(server.endpoint()).unwrap();
+        let register = Packet::new(PacketType::EventRegister, Some("list-sa".to_string()), vec![]);
+        let actual = request(&mut stream, &register);
+        assert_eq!(actual.packet_type, PacketType::EventConfirm);
+
+        let list_sas = Packet::request("list-sas", &Ike { ike: "gw".to_string() }).unwrap();
+        let actual = request(&mut stream, &list_sas);
+        assert_eq!(actual.packet_type, PacketType::Event);
+        assert_eq!(actual.name.as_deref(), Some("list-sa"));
+        assert_eq!(
+            actual.deserialize::<HashMap<String, Sa>>().unwrap()["gw"],
+            Sa {
+                state: "ESTABLISHED".to_string(),
+            }
+        );
+
+        let actual = read_packet(&mut stream).unwrap();
+        assert_eq!(actual.packet_type, PacketType::CmdResponse);
+        assert_eq!(server.requests()[0].deserialize::<Ike>().unwrap(), Ike { ike: "gw".to_string() });
+    }
+
+    #[test]
+    fn emit_registered() {
+        let server = MockServer::bind_tcp().unwrap();
+        let mut registered = Stream::connect(server.endpoint()).unwrap();
+        let mut unregistered = Stream::connect(server.endpoint()).unwrap();
+
+        let register = Packet::new(PacketType::EventRegister, Some("ike-updown".to_string()), vec![]);
+        request(&mut registered, &register);
+
+        server.emit("ike-updown", &Ike { ike: "gw".to_string() }).unwrap();
+        let actual = read_packet(&mut registered).unwrap();
+        assert_eq!(actual, Packet::event("ike-updown", &Ike { ike: "gw".to_string() }).unwrap());
+
+        let actual = request(&mut unregistered, &Packet::request("version", &()).unwrap());
+        assert_eq!(actual.packet_type, PacketType::CmdUnknown);
+    }
+
+    #[test]
+    fn replay() {
+        let server = MockServer::bind_tcp().unwrap();
+        server.handle("version", |_| Reply::new(&Ike { ike: "gw".to_string() }));
+
+        let mut recording = vec![];
+        let stream = Stream::connect(server.endpoint()).unwrap();
+        let mut recorder = Recorder::new(stream, &mut recording);
+        request(&mut recorder, &Packet::request("version", &()).unwrap());
+        request(&mut recorder, &Packet::request("stats", &()).unwrap());
+        drop(recorder);
+
+        let recording: Recording = crate::de::from_slice(&recording).unwrap();
+        let server = ReplayServer::bind_tcp(recording).unwrap();
+        let mut stream = Stream::connect(server.endpoint()).unwrap();
+
+        let actual = request(&mut stream, &Packet::request("version", &()).unwrap());
+        assert_eq!(actual, Packet::response(&Ike { ike: "gw".to_string() }).unwrap());
+        assert_eq!(server.mismatches(), vec![]);
+
+        let actual = request(&mut stream, &Packet::request("reload-settings", &()).unwrap());
+        assert_eq!(actual.packet_type, PacketType::CmdUnknown);
+        assert_eq!(
+            server.mismatches(),
+            vec![Mismatch {
+                index: 2,
+                expected: Packet::request("stats", &()).unwrap(),
+                actual: Packet::request("reload-settings", &()).unwrap(),
+            }]
+        );
+        assert!(read_packet(&mut stream).unwrap_err().is_eof());
+    }
+
+    #[test]
+    fn connections_closed() {
+        let server = MockServer::bind_tcp().unwrap();
+        let mut stream = Stream::connect(server.endpoint()).unwrap();
+        request(&mut stream, &Packet::request("version", &()).unwrap());
+        assert_eq!(server.connections(), 1);
+
+        drop(stream);
+        assert!(eventually(|| server.connections() == 0));
+
+        let server = ReplayServer::bind_tcp(Recording { frames: vec![] }).unwrap();
+        let mut stream = Stream::connect(server.endpoint()).unwrap();
+        assert!(read_packet(&mut stream).unwrap_err().is_eof());
+        assert!(eventually(|| server.shared.connections.lock().unwrap().is_empty()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bind_unix() {
+        let path = std::env::temp_dir().join(format!("serde-vici-{}.sock", std::process::id()));
+        let server = MockServer::bind_unix(&path).unwrap();
+        server.handle("version", |_| Reply::new(&()));
+
+        let mut stream = Stream::connect(server.endpoint()).unwrap();
+        let actual = request(&mut stream, &Packet::request("version", &()).unwrap());
+        assert_eq!(actual, Packet::new(PacketType::CmdResponse, None, vec![]));
+
+        drop(server);
+        assert!(!path.exists());
+    }
+}
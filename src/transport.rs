@@ -0,0 +1,246 @@
+//! Connect to the VICI socket of `charon`.
+//!
+//! The socket is configured in `charon.plugins.vici.socket` as a URI, either `unix:///path/to/socket` or `tcp://host:port`.
+
+use std::{
+    fmt::{self, Display},
+    io,
+    net::{self, Shutdown, TcpListener, TcpStream},
+    str::FromStr,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use crate::error::{Error, ErrorCode};
+
+/// The URI of the VICI socket `charon` listens on by default.
+#[cfg(unix)]
+pub const DEFAULT_URI: &str = "unix:///var/run/charon.vici";
+
+/// The URI of the VICI socket `charon` listens on by default.
+#[cfg(not(unix))]
+pub const DEFAULT_URI: &str = "tcp://127.0.0.1:4502";
+
+/// An address the VICI socket listens on.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Endpoint {
+    /// A TCP socket given as `host:port`.
+    Tcp(String),
+
+    /// A Unix domain socket given as a path.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Default for Endpoint {
+    fn default() -> Self {
+        DEFAULT_URI.parse().unwrap()
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = Error;
+
+    /// Parses a URI in the form of `unix:///path/to/socket` or `tcp://host:port`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |msg: &str| Error::data(ErrorCode::Message(format!("{msg}: {s}")), None, None);
+        let (scheme, rest) = s.split_once("://").ok_or_else(|| invalid("invalid URI"))?;
+
+        match scheme {
+            "tcp" => {
+                let (host, port) = rest.rsplit_once(':').ok_or_else(|| invalid("missing port in URI"))?;
+                if host.is_empty() || port.parse::<u16>().is_err() {
+                    return Err(invalid("invalid host or port in URI"));
+                }
+                Ok(Endpoint::Tcp(rest.to_string()))
+            },
+            #[cfg(unix)]
+            "unix" => {
+                if !rest.starts_with('/') {
+                    return Err(invalid("invalid path in URI"));
+                }
+                Ok(Endpoint::Unix(PathBuf::from(rest)))
+            },
+            _ => Err(invalid("unsupported URI scheme")),
+        }
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => write!(f, "tcp://{addr}"),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// A connected stream to the VICI socket.
+#[derive(Debug)]
+pub enum Stream {
+    /// A TCP stream.
+    Tcp(TcpStream),
+
+    /// A Unix domain socket stream.
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    /// Opens a connection to the given endpoint.
+    pub fn connect(endpoint: &Endpoint) -> io::Result<Self> {
+        match endpoint {
+            Endpoint::Tcp(addr) => TcpStream::connect(addr).map(Stream::Tcp),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => UnixStream::connect(path).map(Stream::Unix),
+        }
+    }
+
+    /// Creates a new independently owned handle to the same connection.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(stream) => stream.try_clone().map(Stream::Tcp),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.try_clone().map(Stream::Unix),
+        }
+    }
+
+    /// Shuts down the read, write, or both halves of the connection.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(how),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(how),
+        }
+    }
+
+    /// Sets the read timeout of the connection.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl io::Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl io::Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "testing"), allow(dead_code))]
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+#[cfg_attr(not(feature = "testing"), allow(dead_code))]
+impl Listener {
+    pub fn bind_tcp(addr: impl net::ToSocketAddrs) -> io::Result<(Self, Endpoint)> {
+        let listener = TcpListener::bind(addr)?;
+        let endpoint = Endpoint::Tcp(listener.local_addr()?.to_string());
+        Ok((Listener::Tcp(listener), endpoint))
+    }
+
+    #[cfg(unix)]
+    pub fn bind_unix(path: impl Into<PathBuf>) -> io::Result<(Self, Endpoint)> {
+        let path = path.into();
+        match std::fs::remove_file(&path) {
+            Ok(()) => {},
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        let endpoint = Endpoint::Unix(path.clone());
+        Ok((Listener::Unix(listener, path), endpoint))
+    }
+
+    pub fn accept(&self) -> io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => listener.accept().map(|(stream, _)| Stream::Tcp(stream)),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.accept().map(|(stream, _)| Stream::Unix(stream)),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_tcp() {
+        let actual: Endpoint = "tcp://127.0.0.1:4502".parse().unwrap();
+        assert_eq!(actual, Endpoint::Tcp("127.0.0.1:4502".to_string()));
+
+        let actual: Endpoint = "tcp://[::1]:4502".parse().unwrap();
+        assert_eq!(actual, Endpoint::Tcp("[::1]:4502".to_string()));
+        assert_eq!(actual.to_string(), "tcp://[::1]:4502");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_unix() {
+        let actual: Endpoint = "unix:///var/run/charon.vici".parse().unwrap();
+        assert_eq!(actual, Endpoint::Unix(PathBuf::from("/var/run/charon.vici")));
+        assert_eq!(actual.to_string(), "unix:///var/run/charon.vici");
+        assert_eq!(actual, Endpoint::default());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for uri in [
+            "/var/run/charon.vici",
+            "tcp://127.0.0.1",
+            "tcp://:4502",
+            "tcp://127.0.0.1:port",
+            "http://127.0.0.1:80",
+        ] {
+            let err = uri.parse::<Endpoint>().unwrap_err();
+            assert!(err.is_data(), "{uri}");
+        }
+    }
+}
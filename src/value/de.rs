@@ -0,0 +1,557 @@
+use std::{fmt, vec};
+
+use serde::de::{self, Deserialize, IntoDeserializer, Unexpected};
+
+use crate::{
+    error::{Error, Result},
+    value::{Section, Value},
+};
+
+impl Value {
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Bytes(v) => Unexpected::Bytes(v),
+            Value::List(_) => Unexpected::Seq,
+            Value::Section(_) => Unexpected::Map,
+        }
+    }
+}
+
+macro_rules! deserialize_number {
+    ($method:ident => $visit:ident) => {
+        #[inline]
+        fn $method<V>(self, visitor: V) -> Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            let value = match self {
+                Value::Bytes(v) => v,
+                v => return Err(de::Error::invalid_type(v.unexpected(), &visitor)),
+            };
+            let value = std::str::from_utf8(&value).map_err::<Error, _>(de::Error::custom)?;
+            let value = value.parse().map_err::<Error, _>(de::Error::custom)?;
+            visitor.$visit(value)
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    #[inline]
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(v) => visitor.visit_byte_buf(v),
+            Value::List(v) => visitor.visit_seq(ListAccess { iter: v.into_iter() }),
+            Value::Section(v) => visitor.visit_map(SectionAccess::new(v)),
+        }
+    }
+
+    #[inline]
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(v) if v == b"yes" => visitor.visit_bool(true),
+            Value::Bytes(v) if v == b"no" => visitor.visit_bool(false),
+            v => Err(de::Error::invalid_value(v.unexpected(), &visitor)),
+        }
+    }
+
+    deserialize_number!(deserialize_i8 => visit_i8);
+    deserialize_number!(deserialize_i16 => visit_i16);
+    deserialize_number!(deserialize_i32 => visit_i32);
+    deserialize_number!(deserialize_i64 => visit_i64);
+    deserialize_number!(deserialize_u8 => visit_u8);
+    deserialize_number!(deserialize_u16 => visit_u16);
+    deserialize_number!(deserialize_u32 => visit_u32);
+    deserialize_number!(deserialize_u64 => visit_u64);
+    deserialize_number!(deserialize_f32 => visit_f32);
+    deserialize_number!(deserialize_f64 => visit_f64);
+    deserialize_number!(deserialize_char => visit_char);
+
+    #[inline]
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    #[inline]
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(v) => match String::from_utf8(v) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => Err(de::Error::invalid_value(Unexpected::Bytes(e.as_bytes()), &visitor)),
+            },
+            v => Err(de::Error::invalid_type(v.unexpected(), &visitor)),
+        }
+    }
+
+    #[inline]
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(v) => visitor.visit_byte_buf(v),
+            v => Err(de::Error::invalid_type(v.unexpected(), &visitor)),
+        }
+    }
+
+    #[inline]
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(v) if v.is_empty() => visitor.visit_none(),
+            v => visitor.visit_some(v),
+        }
+    }
+
+    #[inline]
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn deserialize_unit_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    #[inline]
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::List(v) => visitor.visit_seq(ListAccess { iter: v.into_iter() }),
+            Value::Section(v) => visitor.visit_seq(SectionListAccess { iter: v.into_iter() }),
+            v => Err(de::Error::invalid_type(v.unexpected(), &visitor)),
+        }
+    }
+
+    #[inline]
+    fn deserialize_tuple<V>(self, _: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    #[inline]
+    fn deserialize_tuple_struct<V>(self, _: &'static str, _: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    #[inline]
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self {
+            Value::Section(v) => visitor.visit_map(SectionAccess::new(v)),
+            v => Err(de::Error::invalid_type(v.unexpected(), &visitor)),
+        }
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(self, _: &'static str, _: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(self, _: &'static str, _: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        let variant = match self {
+            Value::Bytes(v) => String::from_utf8(v).map_err::<Error, _>(de::Error::custom)?,
+            v => return Err(de::Error::invalid_type(v.unexpected(), &visitor)),
+        };
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
+    #[inline]
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    #[inline]
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        drop(self);
+        visitor.visit_unit()
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'de> de::IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de> de::IntoDeserializer<'de, Error> for Section {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        Value::Section(self)
+    }
+}
+
+struct ListAccess {
+    iter: vec::IntoIter<Vec<u8>>,
+}
+
+impl<'de> de::SeqAccess<'de> for ListAccess {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.iter.next().map(|v| seed.deserialize(Value::Bytes(v))).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct SectionListAccess {
+    iter: vec::IntoIter<(String, Value)>,
+}
+
+impl<'de> de::SeqAccess<'de> for SectionListAccess {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.iter.next().map(|(_, v)| seed.deserialize(v)).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct SectionAccess {
+    iter: vec::IntoIter<(String, Value)>,
+    value: Option<Value>,
+}
+
+impl SectionAccess {
+    fn new(section: Section) -> Self {
+        let iter = section.into_iter();
+        let value = None;
+        Self { iter, value }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for SectionAccess {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((k, v)) => {
+                self.value = Some(v);
+                seed.deserialize(Value::from(k)).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(v) => seed.deserialize(v),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a VICI value, list, or section")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(if v { "yes" } else { "no" }))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(itoa::Buffer::new().format(v)))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(itoa::Buffer::new().format(v)))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(ryu::Buffer::new().format_finite(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(v))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(v))
+    }
+
+    fn visit_none<E>(self) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::Bytes(vec![]))
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> core::result::Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> core::result::Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::Bytes(vec![]))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> core::result::Result<Self::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+
+        // A sequence of sections is encoded as a section whose elements are named by their indices.
+        if items.iter().any(|item| matches!(item, Value::Section(_))) {
+            return Ok(Value::Section(
+                items.into_iter().enumerate().map(|(i, v)| (i.to_string(), v)).collect(),
+            ));
+        }
+
+        items
+            .into_iter()
+            .map(|item| match item {
+                Value::Bytes(v) => Ok(v),
+                _ => Err(de::Error::custom("nested list")),
+            })
+            .collect::<core::result::Result<_, _>>()
+            .map(Value::List)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> core::result::Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut section = Section::new();
+        while let Some((k, v)) = map.next_entry::<String, Value>()? {
+            section.insert(k, v);
+        }
+        Ok(Value::Section(section))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Section {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match deserializer.deserialize_map(ValueVisitor)? {
+            Value::Section(section) => Ok(section),
+            _ => Err(de::Error::custom("expected a section")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_derive::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{de::from_slice, ser::to_vec};
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    struct Pool {
+        base: String,
+        size: u32,
+        leases: Vec<Lease>,
+        dns: Vec<String>,
+    }
+
+    #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+    struct Lease {
+        address: String,
+        identity: Option<String>,
+        online: bool,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    }
+
+    fn pool() -> Pool {
+        Pool {
+            base: "192.0.2.1".to_string(),
+            size: 4,
+            leases: vec![
+                Lease {
+                    address: "192.0.2.2".to_string(),
+                    identity: Some("identity-01".to_string()),
+                    online: true,
+                    data: vec![0x00, 0xff],
+                },
+                Lease {
+                    address: "192.0.2.3".to_string(),
+                    identity: None,
+                    online: false,
+                    data: vec![],
+                },
+            ],
+            dns: vec!["192.0.2.53".to_string()],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = to_vec(&pool()).unwrap();
+
+        let value: Value = from_slice(&data).unwrap();
+        assert_eq!(to_vec(&value).unwrap(), data);
+
+        let actual = Pool::deserialize(value).unwrap();
+        assert_eq!(actual, pool());
+    }
+
+    #[test]
+    fn section() {
+        let value: Section = from_slice(&to_vec(&pool()).unwrap()).unwrap();
+        assert_eq!(value.keys().collect::<Vec<_>>(), vec!["base", "size", "leases", "dns"]);
+        assert_eq!(value.get("size").and_then(Value::as_str), Some("4"));
+        assert_eq!(value.get("dns").and_then(Value::as_list), Some(&[b"192.0.2.53".to_vec()][..]));
+
+        let leases = value.get("leases").and_then(Value::as_section).unwrap();
+        assert_eq!(leases.keys().collect::<Vec<_>>(), vec!["0", "1"]);
+        assert_eq!(
+            leases.get("1").and_then(Value::as_section).and_then(|lease| lease.get("identity")),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_type() {
+        let value = Value::Section([("size", "four")].into_iter().collect());
+        let err = std::collections::BTreeMap::<String, u32>::deserialize(value).unwrap_err();
+        assert_eq!(err.to_string(), "invalid digit found in string");
+    }
+}
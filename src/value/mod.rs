@@ -1,337 +1,180 @@
-use serde::{ser, Serialize};
+//! The VICI message as a tree of sections, lists, and values.
 
-use crate::error::{Error, Result};
+use std::str;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub(crate) enum FieldType {
-    None,
-    String,
-    Section,
-    List(ListElement),
-}
+pub(crate) use crate::value::ser::to_value;
+
+mod de;
+mod ser;
 
+/// Represents any element of a VICI message.
+///
+/// Values are kept as bytes as they appear on the wire, since VICI does not require them to be valid UTF-8.
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub(crate) enum ListElement {
-    String,
-    Section,
-}
+pub enum Value {
+    /// A value of a key/value pair.
+    Bytes(Vec<u8>),
 
-impl FieldType {
-    #[inline]
-    pub fn from(s: impl Serialize) -> Result<Self> {
-        let mut serializer = FieldTypeSerializer { item: None };
-        s.serialize(&mut serializer)
-    }
-}
+    /// A list of values.
+    List(Vec<Vec<u8>>),
 
-struct FieldTypeSerializer {
-    item: Option<FieldType>,
+    /// A section of named elements.
+    Section(Section),
 }
 
-impl ser::Serializer for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
-
-    #[inline]
-    fn serialize_bool(self, _: bool) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_i8(self, _: i8) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_i16(self, _: i16) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_i32(self, _: i32) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_i64(self, _: i64) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_u8(self, _: u8) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_u16(self, _: u16) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_u32(self, _: u32) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_u64(self, _: u64) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_f32(self, _: f32) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_f64(self, _: f64) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_char(self, _: char) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_str(self, _: &str) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok> {
-        Ok(FieldType::String)
-    }
-
-    #[inline]
-    fn serialize_none(self) -> Result<Self::Ok> {
-        Ok(FieldType::None)
-    }
+/// Represents a VICI section, whose elements are kept in the order they appear.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Section {
+    entries: Vec<(String, Value)>,
+}
 
-    #[inline]
-    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
-    where
-        T: ?Sized + serde::Serialize,
-    {
-        value.serialize(self)
+impl Value {
+    /// Returns the bytes if this is a value of a key/value pair.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(v) => Some(v),
+            _ => None,
+        }
     }
 
-    #[inline]
-    fn serialize_unit(self) -> Result<Self::Ok> {
-        Ok(FieldType::None)
+    /// Returns the string if this is a value of a key/value pair that is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|v| str::from_utf8(v).ok())
     }
 
-    #[inline]
-    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok> {
-        Ok(FieldType::None)
+    /// Returns the items if this is a list.
+    pub fn as_list(&self) -> Option<&[Vec<u8>]> {
+        match self {
+            Value::List(v) => Some(v),
+            _ => None,
+        }
     }
 
-    #[inline]
-    fn serialize_unit_variant(self, _: &'static str, _: u32, _: &'static str) -> Result<Self::Ok> {
-        Ok(FieldType::String)
+    /// Returns the section if this is a section.
+    pub fn as_section(&self) -> Option<&Section> {
+        match self {
+            Value::Section(v) => Some(v),
+            _ => None,
+        }
     }
 
-    #[inline]
-    fn serialize_newtype_struct<T>(self, _: &'static str, _: &T) -> Result<Self::Ok>
-    where
-        T: ?Sized,
-    {
-        Ok(FieldType::String)
+    /// Returns the mutable section if this is a section.
+    pub fn as_section_mut(&mut self) -> Option<&mut Section> {
+        match self {
+            Value::Section(v) => Some(v),
+            _ => None,
+        }
     }
+}
 
-    #[inline]
-    fn serialize_newtype_variant<T>(self, _: &'static str, _: u32, _: &'static str, _: &T) -> Result<Self::Ok>
-    where
-        T: ?Sized,
-    {
-        Ok(FieldType::String)
+impl Section {
+    /// Creates an empty section.
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    #[inline]
-    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq> {
-        Ok(self)
+    /// Returns the number of elements in this section.
+    pub fn len(&self) -> usize {
+        self.entries.len()
     }
 
-    #[inline]
-    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple> {
-        Ok(self)
+    /// Returns true if this section contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
     }
 
-    #[inline]
-    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct> {
-        Ok(self)
+    /// Returns the element with the given name.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
     }
 
-    #[inline]
-    fn serialize_tuple_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeTupleVariant> {
-        Ok(self)
+    /// Returns the mutable element with the given name.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.entries.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v)
     }
 
-    #[inline]
-    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap> {
-        Ok(self)
+    /// Inserts an element, replacing the existing element with the same name in place and returning it.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Value>) -> Option<Value> {
+        let key = key.into();
+        let value = value.into();
+        match self.get_mut(&key) {
+            Some(v) => Some(std::mem::replace(v, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            },
+        }
     }
 
-    #[inline]
-    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct> {
-        Ok(self)
+    /// Removes the element with the given name and returns it.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
     }
 
-    #[inline]
-    fn serialize_struct_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeStructVariant> {
-        Ok(self)
+    /// Returns an iterator over the elements in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
     }
 
-    #[inline]
-    fn collect_str<T>(self, _: &T) -> Result<Self::Ok>
-    where
-        T: ?Sized,
-    {
-        Ok(FieldType::String)
+    /// Returns an iterator over the names of the elements in order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(k, _)| k.as_str())
     }
 }
 
-impl ser::SerializeSeq for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    #[inline]
-    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
-    where
-        T: ?Sized + serde::Serialize,
-    {
-        self.item = Some(value.serialize(&mut **self)?);
-        Ok(())
-    }
-
-    #[inline]
-    fn end(self) -> Result<Self::Ok> {
-        match self.item {
-            Some(FieldType::Section) => Ok(FieldType::List(ListElement::Section)),
-            _ => Ok(FieldType::List(ListElement::String)),
-        }
+impl From<Vec<u8>> for Value {
+    fn from(v: Vec<u8>) -> Self {
+        Value::Bytes(v)
     }
 }
 
-impl ser::SerializeTuple for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    #[inline]
-    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
-    where
-        T: ?Sized + serde::Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
-    }
-
-    #[inline]
-    fn end(self) -> Result<Self::Ok> {
-        ser::SerializeSeq::end(self)
+impl From<&[u8]> for Value {
+    fn from(v: &[u8]) -> Self {
+        Value::Bytes(v.to_vec())
     }
 }
 
-impl ser::SerializeTupleStruct for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    #[inline]
-    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
-    where
-        T: ?Sized + serde::Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
-    }
-
-    #[inline]
-    fn end(self) -> Result<Self::Ok> {
-        ser::SerializeSeq::end(self)
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Bytes(v.into_bytes())
     }
 }
 
-impl ser::SerializeTupleVariant for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    #[inline]
-    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
-    where
-        T: ?Sized + serde::Serialize,
-    {
-        ser::SerializeSeq::serialize_element(self, value)
-    }
-
-    #[inline]
-    fn end(self) -> Result<Self::Ok> {
-        ser::SerializeSeq::end(self)
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Bytes(v.as_bytes().to_vec())
     }
 }
 
-impl ser::SerializeMap for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    #[inline]
-    fn serialize_key<T>(&mut self, _: &T) -> Result<()>
-    where
-        T: ?Sized,
-    {
-        Ok(())
-    }
-
-    #[inline]
-    fn serialize_value<T>(&mut self, _: &T) -> Result<()>
-    where
-        T: ?Sized,
-    {
-        Ok(())
-    }
-
-    #[inline]
-    fn end(self) -> Result<Self::Ok> {
-        Ok(FieldType::Section)
+impl From<Section> for Value {
+    fn from(v: Section) -> Self {
+        Value::Section(v)
     }
 }
 
-impl ser::SerializeStruct for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    #[inline]
-    fn serialize_field<T>(&mut self, _: &'static str, _: &T) -> Result<()>
+impl<K, V> FromIterator<(K, V)> for Section
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    fn from_iter<T>(iter: T) -> Self
     where
-        T: ?Sized,
+        T: IntoIterator<Item = (K, V)>,
     {
-        Ok(())
-    }
-
-    #[inline]
-    fn end(self) -> Result<Self::Ok> {
-        Ok(FieldType::Section)
+        let mut section = Section::new();
+        for (k, v) in iter {
+            section.insert(k, v);
+        }
+        section
     }
 }
 
-impl ser::SerializeStructVariant for &mut FieldTypeSerializer {
-    type Ok = FieldType;
-    type Error = Error;
-
-    #[inline]
-    fn serialize_field<T>(&mut self, _: &'static str, _: &T) -> Result<()>
-    where
-        T: ?Sized,
-    {
-        Ok(())
-    }
+impl IntoIterator for Section {
+    type Item = (String, Value);
+    type IntoIter = std::vec::IntoIter<(String, Value)>;
 
-    #[inline]
-    fn end(self) -> Result<Self::Ok> {
-        Ok(FieldType::Section)
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
     }
 }
@@ -0,0 +1,72 @@
+use std::str;
+
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+
+use crate::{
+    de::from_slice,
+    error,
+    ser::to_vec,
+    value::{Section, Value},
+};
+
+/// Convert a `T` into a `Value`.
+///
+/// The value is built from the message the [`Serializer`](crate::Serializer) writes, so it is always a section and follows the same
+/// rules, for example `None` values are omitted and sequences of structs become sections named by their indices.
+///
+/// # Errors
+/// Serialization can fail if `T`'s implementation of `Serialize` decides to fail, or if `T` cannot be represented as a VICI section.
+pub fn to_value<T>(value: &T) -> error::Result<Value>
+where
+    T: ?Sized + Serialize,
+{
+    let section: Section = from_slice(&to_vec(value)?)?;
+    Ok(Value::Section(section))
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        // Human-readable formats such as JSON get a string whenever possible.
+        match str::from_utf8(self.0) {
+            Ok(s) => serializer.serialize_str(s),
+            Err(_) => serializer.serialize_bytes(self.0),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            Value::Bytes(v) => Bytes(v).serialize(serializer),
+            Value::List(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+                for item in v {
+                    seq.serialize_element(&Bytes(item))?;
+                }
+                seq.end()
+            },
+            Value::Section(v) => v.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Section {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (k, v) in &self.entries {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}